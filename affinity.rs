@@ -0,0 +1,254 @@
+use std::{fmt, fs, io, path::Path};
+
+const SYSFS_CPU: &str = "/sys/devices/system/cpu";
+
+/// One online logical CPU and where it sits in the machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cpu {
+    pub id: usize,
+    pub core: usize,
+    pub package: usize,
+    /// Position of this CPU among the hardware threads of its core (0 for the first sibling).
+    pub smt: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Placement {
+    /// Leave scheduling to the OS.
+    None,
+    /// Fill one package before the next, one hardware thread per core before any sibling.
+    Compact,
+    /// Round-robin across packages, one hardware thread per core before any sibling.
+    Scatter,
+    /// Fill both SMT siblings of a core before moving to the next core.
+    SmtFirst,
+    /// Pin thread `i` to `cpus[i]`; runs with more threads than listed CPUs stay unpinned.
+    List(Vec<usize>),
+}
+
+impl Placement {
+    /// Parses `none`, `compact`, `scatter`, `smt` or an explicit CPU list such as `0-3,8`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "none" => Placement::None,
+            "compact" => Placement::Compact,
+            "scatter" => Placement::Scatter,
+            "smt" => Placement::SmtFirst,
+            list => Placement::List(
+                parse_cpu_list(list).ok_or_else(|| format!("invalid placement `{list}`"))?,
+            ),
+        })
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placement::None => f.write_str("none"),
+            Placement::Compact => f.write_str("compact"),
+            Placement::Scatter => f.write_str("scatter"),
+            Placement::SmtFirst => f.write_str("smt"),
+            Placement::List(cpus) => {
+                // Back in the kernel's list format, so `--pin` takes it as printed.
+                let mut rest = cpus.as_slice();
+                let mut sep = "";
+                while let Some(&lo) = rest.first() {
+                    let run = rest.windows(2).take_while(|w| w[1] == w[0] + 1).count();
+                    match run {
+                        0 => write!(f, "{sep}{lo}")?,
+                        _ => write!(f, "{sep}{lo}-{}", lo + run)?,
+                    }
+                    rest = &rest[run + 1..];
+                    sep = ",";
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses the kernel's CPU list format (`0-3,8,10-11`).
+pub fn parse_cpu_list(s: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+    for part in s.trim().split(',') {
+        match part.split_once('-') {
+            Some((lo, hi)) => {
+                let (lo, hi): (usize, usize) = (lo.parse().ok()?, hi.parse().ok()?);
+                if lo > hi {
+                    return None;
+                }
+                cpus.extend(lo..=hi);
+            }
+            None => cpus.push(part.parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+fn read_usize(path: &Path) -> io::Result<usize> {
+    fs::read_to_string(path)?
+        .trim()
+        .parse()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[derive(Clone, Debug)]
+pub struct Topology {
+    pub cpus: Vec<Cpu>,
+}
+
+impl Topology {
+    /// Reads the online CPUs and their core/package ids from `/sys/devices/system/cpu`.
+    pub fn detect() -> io::Result<Self> {
+        let root = Path::new(SYSFS_CPU);
+        let online = fs::read_to_string(root.join("online"))?;
+        let ids = parse_cpu_list(&online)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, online.clone()))?;
+        let mut cpus = Vec::with_capacity(ids.len());
+        for id in ids {
+            let topo = root.join(format!("cpu{id}/topology"));
+            cpus.push(Cpu {
+                id,
+                core: read_usize(&topo.join("core_id"))?,
+                package: read_usize(&topo.join("physical_package_id"))?,
+                smt: 0,
+            });
+        }
+        for i in 0..cpus.len() {
+            let c = cpus[i];
+            cpus[i].smt = cpus[..i]
+                .iter()
+                .filter(|o| o.core == c.core && o.package == c.package)
+                .count();
+        }
+        Ok(Topology { cpus })
+    }
+
+    /// Returns the CPU each of `threads` workers should be pinned to, or `None` to leave
+    /// placement to the OS. Under a policy, workers beyond the number of CPUs wrap around;
+    /// an explicit list names one CPU per worker, so it pins nothing if it is too short.
+    pub fn assign(&self, placement: &Placement, threads: usize) -> Option<Vec<usize>> {
+        let mut order = self.cpus.clone();
+        match placement {
+            Placement::None => return None,
+            Placement::List(list) => {
+                return (threads <= list.len()).then(|| list[..threads].to_vec());
+            }
+            Placement::Compact => order.sort_by_key(|c| (c.package, c.smt, c.core, c.id)),
+            Placement::SmtFirst => order.sort_by_key(|c| (c.package, c.core, c.smt, c.id)),
+            Placement::Scatter => {
+                // Rank cores within their package so the nth core of every package comes
+                // before the (n+1)th core of any of them.
+                let rank = |c: &Cpu| {
+                    let mut cores: Vec<usize> = self
+                        .cpus
+                        .iter()
+                        .filter(|o| o.package == c.package)
+                        .map(|o| o.core)
+                        .collect();
+                    cores.sort_unstable();
+                    cores.dedup();
+                    cores.binary_search(&c.core).unwrap()
+                };
+                order.sort_by_key(|c| (c.smt, rank(c), c.package, c.id));
+            }
+        }
+        Some(order.iter().cycle().take(threads).map(|c| c.id).collect())
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe extern "C" {
+    fn sched_setaffinity(pid: i32, cpusetsize: usize, mask: *const u64) -> i32;
+}
+
+/// Pins the calling thread to `cpu`.
+#[cfg(target_os = "linux")]
+pub fn pin_current(cpu: usize) -> io::Result<()> {
+    // Matches glibc's `cpu_set_t`, which covers 1024 CPUs.
+    let mut mask = [0u64; 16];
+    *mask
+        .get_mut(cpu / 64)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cpu id out of range"))? |=
+        1 << (cpu % 64);
+    if unsafe { sched_setaffinity(0, size_of_val(&mask), mask.as_ptr()) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "thread pinning is only supported on Linux",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two packages of two cores with two hardware threads each, numbered the way Linux
+    /// usually does: first threads of every core, then their siblings.
+    fn two_by_two_by_two() -> Topology {
+        let cpus = (0..8)
+            .map(|id| Cpu {
+                id,
+                core: id % 2,
+                package: id / 2 % 2,
+                smt: id / 4,
+            })
+            .collect();
+        Topology { cpus }
+    }
+
+    #[test]
+    fn cpu_lists() {
+        assert_eq!(parse_cpu_list("0-3,8"), Some(vec![0, 1, 2, 3, 8]));
+        assert_eq!(parse_cpu_list("0-1,4-5\n"), Some(vec![0, 1, 4, 5]));
+        assert_eq!(parse_cpu_list("7"), Some(vec![7]));
+        for bad in ["", "3-1", "0-3,", ",1", "0,,1", "1-", "a", "-1"] {
+            assert_eq!(parse_cpu_list(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn placements_print_as_parsed() {
+        let policies = ["none", "compact", "scatter", "smt"];
+        for s in policies.into_iter().chain(["0-3,8", "5", "1,3-4,9-11"]) {
+            assert_eq!(Placement::parse(s).unwrap().to_string(), s);
+        }
+        assert_eq!(Placement::List(vec![2, 0, 1]).to_string(), "2,0-1");
+        assert!(Placement::parse("3-1").is_err());
+    }
+
+    #[test]
+    fn policies_order_cpus() {
+        let topology = two_by_two_by_two();
+        let assign = |p: &str, threads| topology.assign(&Placement::parse(p).unwrap(), threads);
+        assert_eq!(assign("none", 4), None);
+        // One package at a time, and every core once before any sibling.
+        assert_eq!(assign("compact", 8), Some(vec![0, 1, 4, 5, 2, 3, 6, 7]));
+        // Both siblings of a core back to back.
+        assert_eq!(assign("smt", 8), Some(vec![0, 4, 1, 5, 2, 6, 3, 7]));
+        // Alternating packages, and every core once before any sibling.
+        assert_eq!(assign("scatter", 8), Some(vec![0, 2, 1, 3, 4, 6, 5, 7]));
+        // Policies wrap around once they run out of CPUs.
+        assert_eq!(
+            assign("scatter", 10),
+            Some(vec![0, 2, 1, 3, 4, 6, 5, 7, 0, 2])
+        );
+        assert_eq!(assign("compact", 3), Some(vec![0, 1, 4]));
+    }
+
+    #[test]
+    fn explicit_lists_pin_one_worker_per_cpu() {
+        let topology = two_by_two_by_two();
+        let list = Placement::parse("6,2-3").unwrap();
+        assert_eq!(topology.assign(&list, 2), Some(vec![6, 2]));
+        assert_eq!(topology.assign(&list, 3), Some(vec![6, 2, 3]));
+        assert_eq!(topology.assign(&list, 4), None);
+    }
+}
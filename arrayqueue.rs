@@ -0,0 +1,195 @@
+use std::{cell::UnsafeCell, mem::MaybeUninit};
+
+use crossbeam_utils::CachePadded;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+
+struct Slot<T> {
+    /// `2 * lap` while free for the push of that lap, `2 * lap + 1` once written and waiting
+    /// for the pop of that lap. Keeping the two states apart per lap, rather than counting in
+    /// positions, is what lets a single-slot queue tell full from free.
+    seq: AtomicUsize,
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+/// Bounded lock-free MPMC queue over a ring of sequence-numbered slots (Vyukov).
+///
+/// Every slot is allocated up front, so `push` and `pop` never allocate; a full queue hands
+/// the element back instead of growing, which gives producers back-pressure.
+pub struct ArrayQueue<T> {
+    head: CachePadded<AtomicUsize>,
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[Slot<T>]>,
+}
+
+unsafe impl<T> Sync for ArrayQueue<T> where T: Send {}
+unsafe impl<T> Send for ArrayQueue<T> where T: Send {}
+
+impl<T> ArrayQueue<T> {
+    /// Creates a queue holding at most `capacity` elements.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be non-zero");
+        let buffer = (0..capacity)
+            .map(|_| Slot {
+                seq: AtomicUsize::new(0),
+                data: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+        Self {
+            head: CachePadded::new(AtomicUsize::new(0)),
+            tail: CachePadded::new(AtomicUsize::new(0)),
+            buffer,
+        }
+    }
+
+    /// Pushes `t` onto the tail, or returns it if the queue is full.
+    pub fn push(&self, t: T) -> Result<(), T> {
+        let mut pos = self.tail.load(Ordering::Relaxed);
+        loop {
+            let (slot, lap) = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            match seq.wrapping_sub(2 * lap) as isize {
+                0 => match self.tail.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.data.get()).write(t) };
+                        slot.seq.store(2 * lap + 1, Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot still holds the element pushed a lap ago: full.
+                d if d < 0 => return Err(t),
+                // Another producer already claimed `pos`.
+                _ => pos = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Pops from the head, or returns `None` if the queue is empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.head.load(Ordering::Relaxed);
+        loop {
+            let (slot, lap) = self.slot(pos);
+            let seq = slot.seq.load(Ordering::Acquire);
+            match seq.wrapping_sub(2 * lap + 1) as isize {
+                0 => match self.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let t = unsafe { (*slot.data.get()).assume_init_read() };
+                        // Free the slot for the push one lap ahead.
+                        slot.seq.store(2 * lap + 2, Ordering::Release);
+                        return Some(t);
+                    }
+                    Err(current) => pos = current,
+                },
+                // Nothing has been pushed at `pos` yet: empty.
+                d if d < 0 => return None,
+                // Another consumer already took `pos`.
+                _ => pos = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    fn slot(&self, pos: usize) -> (&Slot<T>, usize) {
+        let cap = self.buffer.len();
+        (&self.buffer[pos % cap], pos / cap)
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Number of elements in the queue. Only a snapshot while other threads are pushing or
+    /// popping.
+    pub fn len(&self) -> usize {
+        loop {
+            let tail = self.tail.load(Ordering::SeqCst);
+            let head = self.head.load(Ordering::SeqCst);
+            // Retry until `head` and `tail` come from the same moment.
+            if self.tail.load(Ordering::SeqCst) == tail {
+                return tail.wrapping_sub(head).min(self.capacity());
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl<T> Drop for ArrayQueue<T> {
+    fn drop(&mut self) {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Relaxed);
+        let mut pos = head;
+        while pos != tail {
+            let slot = &mut self.buffer[pos % self.buffer.len()];
+            unsafe { slot.data.get_mut().assume_init_drop() };
+            pos = pos.wrapping_add(1);
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn full_and_empty_across_laps() {
+        let queue = ArrayQueue::new(3);
+        assert!(queue.is_empty());
+        assert_eq!(queue.pop(), None);
+        let mut next = 0;
+        for lap in 0..5 {
+            while queue.push(next).is_ok() {
+                next += 1;
+            }
+            assert!(queue.is_full());
+            assert_eq!(queue.push(-1), Err(-1));
+            // Leave one behind so every lap starts at a different slot.
+            for expected in next - 3..next - 1 {
+                assert_eq!(queue.pop(), Some(expected), "lap {lap}");
+            }
+            assert_eq!(queue.len(), 1);
+        }
+        assert_eq!(queue.pop(), Some(next - 1));
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn drop_drops_what_is_left() {
+        let counts = DropCounts::new(16);
+        let queue = ArrayQueue::new(4);
+        // Wrap the occupied range around the end of the buffer before dropping.
+        for v in 0..3 {
+            assert!(queue.push(counts.track(v)).is_ok());
+        }
+        drop(queue.pop());
+        drop(queue.pop());
+        for v in 3..6 {
+            assert!(queue.push(counts.track(v)).is_ok());
+        }
+        drop(queue.push(counts.track(6)).unwrap_err());
+        assert_eq!(counts.dropped(), 3);
+        drop(queue);
+        counts.assert_all_dropped();
+    }
+}
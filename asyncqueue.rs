@@ -0,0 +1,303 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use crate::{
+    msqueue::Queue,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering, fence},
+    },
+};
+
+#[derive(Default)]
+struct Wakers {
+    next_key: u64,
+    /// Tasks waiting for a push, oldest first, keyed so a dropped [`Pop`] can take its entry
+    /// back out.
+    entries: VecDeque<(u64, Waker)>,
+}
+
+/// [`Queue`] for async code: [`AsyncQueue::pop`] returns a future that registers the task's
+/// `Waker` while the queue is empty and is woken by the next push.
+///
+/// Only `std::task` is used, so it works under any executor. A push takes the waker lock only
+/// when some task is actually waiting; otherwise it costs one fence over [`Queue::push`]. It
+/// wakes only the longest-waiting task. A woken future that is dropped, or that comes back
+/// having taken some other element, passes the wakeup on to the next waiter if elements are
+/// left, so none is stranded while tasks wait.
+pub struct AsyncQueue<T> {
+    queue: Queue<T>,
+    /// Number of entries in `wakers`, readable without the lock.
+    waiting: AtomicUsize,
+    wakers: Mutex<Wakers>,
+}
+
+impl<T> Default for AsyncQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> AsyncQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            queue: Queue::new(),
+            waiting: AtomicUsize::new(0),
+            wakers: Mutex::new(Wakers::default()),
+        }
+    }
+
+    /// Pushes `t` onto the tail and wakes the task that has waited longest in
+    /// [`AsyncQueue::pop`].
+    pub fn push(&self, t: T) {
+        self.queue.push(t);
+        // Pairs with the fence in `poll_pop`: either we see its registration here, or its
+        // re-check after registering sees our element.
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        self.wake_one();
+    }
+
+    /// Takes the oldest entry out and wakes it; its owner finds the entry gone when it
+    /// unregisters, which is how it learns it was woken.
+    fn wake_one(&self) {
+        let waker = {
+            let mut wakers = self.wakers.lock().unwrap();
+            let waker = wakers.entries.pop_front();
+            self.waiting.store(wakers.entries.len(), Ordering::SeqCst);
+            waker
+        };
+        if let Some((_, waker)) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Pops from the head, waiting for a push while the queue is empty.
+    pub fn pop(&self) -> Pop<'_, T> {
+        Pop {
+            queue: self,
+            key: None,
+        }
+    }
+
+    /// Pops from the head without waiting.
+    pub fn try_pop(&self) -> Option<T> {
+        self.queue.pop()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Stream of popped elements; it never ends, since the queue cannot be closed.
+    pub fn stream(&self) -> PopStream<'_, T> {
+        PopStream {
+            queue: self,
+            key: None,
+        }
+    }
+
+    /// Shared by [`Pop`] and [`PopStream`]; `key` names the caller's waker entry, if any.
+    fn poll_pop(&self, key: &mut Option<u64>, cx: &mut Context<'_>) -> Poll<T> {
+        if let Some(t) = self.queue.pop() {
+            self.unregister(key);
+            return Poll::Ready(t);
+        }
+
+        let mut wakers = self.wakers.lock().unwrap();
+        // A push may have drained our old entry already, in which case we register anew.
+        match wakers.entries.iter_mut().find(|(k, _)| Some(*k) == *key) {
+            Some((_, waker)) => waker.clone_from(cx.waker()),
+            None => {
+                let k = wakers.next_key;
+                wakers.next_key += 1;
+                wakers.entries.push_back((k, cx.waker().clone()));
+                *key = Some(k);
+            }
+        }
+        self.waiting.store(wakers.entries.len(), Ordering::SeqCst);
+        drop(wakers);
+        fence(Ordering::SeqCst);
+
+        match self.queue.pop() {
+            Some(t) => {
+                self.unregister(key);
+                Poll::Ready(t)
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    fn unregister(&self, key: &mut Option<u64>) {
+        let Some(k) = key.take() else {
+            return;
+        };
+        let mut wakers = self.wakers.lock().unwrap();
+        let before = wakers.entries.len();
+        wakers.entries.retain(|(e, _)| *e != k);
+        self.waiting.store(wakers.entries.len(), Ordering::SeqCst);
+        let woken = wakers.entries.len() == before;
+        drop(wakers);
+        // A push took our entry out to wake us. We are leaving without the element it was
+        // for, or with another one, so if anything is still queued the next waiter gets the
+        // wakeup instead. The push made its element visible before taking the entry, and we
+        // saw the entry gone under the same lock, so `is_empty` cannot miss that element.
+        if woken && !self.queue.is_empty() {
+            self.wake_one();
+        }
+    }
+}
+
+/// Future returned by [`AsyncQueue::pop`].
+pub struct Pop<'a, T> {
+    queue: &'a AsyncQueue<T>,
+    key: Option<u64>,
+}
+
+impl<T> Future for Pop<'_, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = self.get_mut();
+        this.queue.poll_pop(&mut this.key, cx)
+    }
+}
+
+impl<T> Drop for Pop<'_, T> {
+    fn drop(&mut self) {
+        self.queue.unregister(&mut self.key);
+    }
+}
+
+/// Stream returned by [`AsyncQueue::stream`]. Implements `futures::Stream` with the
+/// `futures` feature.
+pub struct PopStream<'a, T> {
+    queue: &'a AsyncQueue<T>,
+    key: Option<u64>,
+}
+
+impl<T> PopStream<'_, T> {
+    /// Same as `Stream::poll_next`, for callers without the `futures` feature.
+    pub fn poll_next_item(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        self.queue.poll_pop(&mut self.key, cx)
+    }
+}
+
+impl<T> Drop for PopStream<'_, T> {
+    fn drop(&mut self) {
+        self.queue.unregister(&mut self.key);
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for PopStream<'_, T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_next_item(cx).map(Some)
+    }
+}
+
+/// The queue is unbounded, so it is always ready for another element and has nothing to
+/// flush.
+#[cfg(feature = "futures")]
+impl<T> futures_sink::Sink<T> for &AsyncQueue<T> {
+    type Error = std::convert::Infallible;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, t: T) -> Result<(), Self::Error> {
+        self.push(t);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::{Arc, atomic::AtomicUsize},
+        task::Wake,
+    };
+
+    use super::*;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn push_wakes_one_pop_and_a_dropped_pop_passes_it_on() {
+        let queue = AsyncQueue::new();
+        let counts: Vec<_> = (0..3)
+            .map(|_| Arc::new(CountingWaker(AtomicUsize::new(0))))
+            .collect();
+        let wakers: Vec<_> = counts.iter().map(|c| Waker::from(Arc::clone(c))).collect();
+        let woken = || -> Vec<_> { counts.iter().map(|c| c.0.load(Ordering::SeqCst)).collect() };
+
+        let mut first = Box::pin(queue.pop());
+        let mut second = pin!(queue.pop());
+        let mut third = pin!(queue.pop());
+        let cx = |i| Context::from_waker(&wakers[i]);
+        assert!(first.as_mut().poll(&mut cx(0)).is_pending());
+        assert!(second.as_mut().poll(&mut cx(1)).is_pending());
+        assert!(third.as_mut().poll(&mut cx(2)).is_pending());
+
+        queue.push(1);
+        assert_eq!(woken(), [1, 0, 0]);
+        // Woken, but gone before it took the element: the next waiter is woken instead.
+        drop(first);
+        assert_eq!(woken(), [1, 1, 0]);
+        assert_eq!(second.as_mut().poll(&mut cx(1)), Poll::Ready(1));
+        assert!(third.as_mut().poll(&mut cx(2)).is_pending());
+        assert_eq!(woken(), [1, 1, 0]);
+
+        queue.push(2);
+        assert_eq!(woken(), [1, 1, 1]);
+        assert_eq!(third.as_mut().poll(&mut cx(2)), Poll::Ready(2));
+    }
+
+    #[test]
+    fn pop_woken_for_one_element_that_takes_another_passes_the_wakeup_on() {
+        let queue = AsyncQueue::new();
+        let counts: Vec<_> = (0..2)
+            .map(|_| Arc::new(CountingWaker(AtomicUsize::new(0))))
+            .collect();
+        let wakers: Vec<_> = counts.iter().map(|c| Waker::from(Arc::clone(c))).collect();
+        let woken = || -> Vec<_> { counts.iter().map(|c| c.0.load(Ordering::SeqCst)).collect() };
+        let cx = |i| Context::from_waker(&wakers[i]);
+
+        let mut first = pin!(queue.pop());
+        let mut second = pin!(queue.pop());
+        assert!(first.as_mut().poll(&mut cx(0)).is_pending());
+        assert!(second.as_mut().poll(&mut cx(1)).is_pending());
+        // As if this push had checked for waiters just before they registered.
+        queue.queue.push(1);
+        queue.push(2);
+        assert_eq!(woken(), [1, 0]);
+        // The first pop was woken for 2 but takes 1, so 2 would be stranded without a hand-off.
+        assert_eq!(first.as_mut().poll(&mut cx(0)), Poll::Ready(1));
+        assert_eq!(woken(), [1, 1]);
+        assert_eq!(second.as_mut().poll(&mut cx(1)), Poll::Ready(2));
+    }
+}
@@ -0,0 +1,958 @@
+use std::{
+    cell::UnsafeCell,
+    fmt::Write as _,
+    hint::{self, black_box},
+    io,
+    sync::{
+        Barrier,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    affinity::{self, Placement, Topology},
+    clhlock::Clhlock,
+    elimination::EliminationStack,
+    faaqueue::FaaQueue,
+    mcslock::McsLock,
+    mcsparklock::McsParkLock,
+    msqueue::Queue,
+    rawlock::RawLock,
+    spsc::{self, Consumer, Producer},
+    ticketlock::TicketLock,
+    treiberstack::Stack,
+    twolockqueue::TwoLockQueue,
+};
+
+pub const USAGE: &str = "\
+usage: prac <target|all> [options]
+
+targets: ticket, clh, mcs, mcspark, stack, elimstack, queue, faaqueue, twolock-ticket,
+twolock-clh, twolock-mcs, twolock-mcspark, spsc, spsc-queue
+
+elimstack runs the push/pop workload of stack on the elimination-backoff stack.
+
+twolock-* run the two-lock queue with the named lock guarding each end.
+
+spsc and spsc-queue pass elements from one producer thread to one consumer thread, through
+the spsc ring and through the MPMC queue respectively; they always run with two threads.
+
+queue and faaqueue sweep 1,2,4,8,16,32,64 threads unless --threads is given: the
+fetch-and-add queue is meant to pull ahead of the Michael-Scott queue as contention grows,
+so the comparison needs the high end.
+
+options:
+    --threads <list>     comma-separated thread counts to sweep (default: 1,2,4,8; see
+                         above for queue and faaqueue)
+    --iters <n>          operations per thread (default: 100000)
+    --duration-ms <ms>   run each point for a fixed time instead of a fixed op count
+    --cs <n>             spin iterations inside the critical section (default: 0)
+    --ncs <n>            spin iterations between critical sections (default: 0)
+    --format <fmt>       csv or json (default: csv)
+    --pin <placement>    none, compact, scatter, smt or a CPU list like 0-3,8 with at least
+                         one CPU per thread (default: none)";
+
+const TARGETS: [&str; 14] = [
+    "ticket",
+    "clh",
+    "mcs",
+    "mcspark",
+    "stack",
+    "elimstack",
+    "queue",
+    "faaqueue",
+    "twolock-ticket",
+    "twolock-clh",
+    "twolock-mcs",
+    "twolock-mcspark",
+    "spsc",
+    "spsc-queue",
+];
+
+/// Targets with a fixed producer/consumer pair instead of a thread-count sweep.
+const PIPELINES: [&str; 2] = ["spsc", "spsc-queue"];
+
+/// Thread counts swept when `--threads` is not given.
+const DEFAULT_SWEEP: [usize; 4] = [1, 2, 4, 8];
+
+/// The queues compared across contention levels, and their default sweep.
+const QUEUES: [&str; 2] = ["queue", "faaqueue"];
+const QUEUE_SWEEP: [usize; 7] = [1, 2, 4, 8, 16, 32, 64];
+
+const PIPE_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub targets: Vec<&'static str>,
+    /// `None` sweeps each target's default thread counts.
+    pub threads: Option<Vec<usize>>,
+    pub iters: u64,
+    pub duration: Option<Duration>,
+    pub cs_work: u32,
+    pub ncs_work: u32,
+    pub format: Format,
+    pub placement: Placement,
+}
+
+impl Config {
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut args = args.into_iter();
+        let target = args.next().ok_or("missing subcommand")?;
+        let targets = match target.as_str() {
+            "all" => TARGETS.to_vec(),
+            t => vec![
+                *TARGETS
+                    .iter()
+                    .find(|l| **l == t)
+                    .ok_or_else(|| format!("unknown subcommand `{t}`"))?,
+            ],
+        };
+        let mut cfg = Config {
+            targets,
+            threads: None,
+            iters: 100_000,
+            duration: None,
+            cs_work: 0,
+            ncs_work: 0,
+            format: Format::Csv,
+            placement: Placement::None,
+        };
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for `{flag}`"))?;
+            match flag.as_str() {
+                "--threads" => {
+                    let threads: Vec<usize> = value
+                        .split(',')
+                        .map(|t| parse(&flag, t))
+                        .collect::<Result<_, _>>()?;
+                    if threads.contains(&0) {
+                        return Err("thread counts must be positive".into());
+                    }
+                    cfg.threads = Some(threads);
+                }
+                "--iters" => cfg.iters = parse(&flag, &value)?,
+                "--duration-ms" => {
+                    cfg.duration = Some(Duration::from_millis(parse(&flag, &value)?))
+                }
+                "--cs" => cfg.cs_work = parse(&flag, &value)?,
+                "--ncs" => cfg.ncs_work = parse(&flag, &value)?,
+                "--format" => {
+                    cfg.format = match value.as_str() {
+                        "csv" => Format::Csv,
+                        "json" => Format::Json,
+                        f => return Err(format!("unknown format `{f}`")),
+                    }
+                }
+                "--pin" => cfg.placement = Placement::parse(&value)?,
+                f => return Err(format!("unknown option `{f}`")),
+            }
+        }
+        // A list pins one worker per CPU; a run with more workers would quietly go unpinned,
+        // which is not the experiment that was asked for.
+        if let Placement::List(list) = &cfg.placement {
+            for &target in &cfg.targets {
+                let wanted = cfg.sweep(target).iter().copied().max().unwrap_or(0);
+                let asker = if PIPELINES.contains(&target) {
+                    format!("`{target}` runs")
+                } else if cfg.threads.is_none() && QUEUES.contains(&target) {
+                    format!("`{target}` sweeps up to")
+                } else {
+                    "`--threads` asks for".to_string()
+                };
+                if wanted > list.len() {
+                    return Err(format!(
+                        "`--pin` lists {} CPUs but {asker} {wanted}",
+                        list.len()
+                    ));
+                }
+            }
+        }
+        Ok(cfg)
+    }
+
+    /// The thread counts `target` runs with.
+    pub fn sweep(&self, target: &str) -> &[usize] {
+        if PIPELINES.contains(&target) {
+            &[2]
+        } else if let Some(threads) = &self.threads {
+            threads
+        } else if QUEUES.contains(&target) {
+            &QUEUE_SWEEP
+        } else {
+            &DEFAULT_SWEEP
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value `{value}` for `{flag}`"))
+}
+
+/// Burns roughly `n` iterations of CPU without touching shared memory.
+#[inline]
+fn spin_work(n: u32) {
+    for i in 0..n {
+        black_box(i);
+    }
+}
+
+/// Log-linear latency histogram: 16 linear sub-buckets per power of two, so every recorded
+/// value is reported within ~6% without keeping one sample per operation.
+struct Histogram {
+    counts: Vec<u64>,
+    max: u64,
+}
+
+impl Histogram {
+    const SUB: u32 = 4;
+
+    fn new() -> Self {
+        Self {
+            counts: vec![0; (64 << Self::SUB) as usize],
+            max: 0,
+        }
+    }
+
+    fn index(v: u64) -> usize {
+        if v < 1 << Self::SUB {
+            return v as usize;
+        }
+        let exp = 63 - v.leading_zeros();
+        let sub = (v >> (exp - Self::SUB)) & ((1 << Self::SUB) - 1);
+        (((exp - Self::SUB + 1) << Self::SUB) as u64 + sub) as usize
+    }
+
+    fn value(idx: usize) -> u64 {
+        let idx = idx as u64;
+        let bucket = idx >> Self::SUB;
+        if bucket == 0 {
+            return idx;
+        }
+        let exp = bucket + Self::SUB as u64 - 1;
+        let sub = idx & ((1 << Self::SUB) - 1);
+        (1 << exp) | (sub << (exp - Self::SUB as u64))
+    }
+
+    fn record(&mut self, v: u64) {
+        self.counts[Self::index(v)] += 1;
+        self.max = self.max.max(v);
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {
+            *a += b;
+        }
+        self.max = self.max.max(other.max);
+    }
+
+    fn percentile(&self, p: f64) -> u64 {
+        let total: u64 = self.counts.iter().sum();
+        let rank = ((total as f64 * p).ceil() as u64).max(1);
+        // The top rank is the largest sample, which is kept exactly.
+        if rank >= total {
+            return self.max;
+        }
+        let mut seen = 0;
+        for (i, c) in self.counts.iter().enumerate() {
+            seen += c;
+            if seen >= rank {
+                return Self::value(i).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+/// One operation the harness times in a loop on every thread.
+trait Workload: Sync {
+    /// Runs one operation on worker `thread` and returns the nanoseconds spent waiting to get
+    /// in, or `None` if the worker can make no further progress and should stop.
+    fn op(&self, thread: usize, cs_work: u32) -> Option<u64>;
+
+    /// Called on worker `thread` once it has run its last operation.
+    fn finish(&self, _thread: usize) {}
+
+    /// Checks the shared state against the total number of completed operations.
+    fn verify(&self, _ops: u64) {}
+}
+
+struct LockWorkload<L> {
+    lock: L,
+    counter: UnsafeCell<u64>,
+}
+
+unsafe impl<L: Sync> Sync for LockWorkload<L> {}
+
+impl<L: RawLock> Workload for LockWorkload<L> {
+    fn op(&self, _thread: usize, cs_work: u32) -> Option<u64> {
+        let start = Instant::now();
+        let token = self.lock.lock();
+        let waited = start.elapsed().as_nanos() as u64;
+        // A plain read-modify-write: a broken lock shows up as lost increments in `verify`.
+        unsafe { *self.counter.get() += 1 };
+        spin_work(cs_work);
+        self.lock.unlock(token);
+        Some(waited)
+    }
+
+    fn verify(&self, ops: u64) {
+        let counted = unsafe { *self.counter.get() };
+        assert_eq!(counted, ops, "lock lost updates: mutual exclusion violated");
+    }
+}
+
+/// The stacks the `stack` and `elimstack` targets compare.
+trait BenchStack: Sync {
+    fn push(&self, v: u64);
+    fn pop(&self) -> Option<u64>;
+    fn is_empty(&self) -> bool;
+}
+
+impl BenchStack for Stack<u64> {
+    fn push(&self, v: u64) {
+        Stack::push(self, v)
+    }
+
+    fn pop(&self) -> Option<u64> {
+        Stack::pop(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        Stack::is_empty(self)
+    }
+}
+
+impl BenchStack for EliminationStack<u64> {
+    fn push(&self, v: u64) {
+        EliminationStack::push(self, v)
+    }
+
+    fn pop(&self) -> Option<u64> {
+        EliminationStack::pop(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        EliminationStack::is_empty(self)
+    }
+}
+
+/// Each op pushes one element and pops one back, so the structure never runs dry.
+struct StackWorkload<S> {
+    stack: S,
+}
+
+impl<S: BenchStack> Workload for StackWorkload<S> {
+    fn op(&self, _thread: usize, cs_work: u32) -> Option<u64> {
+        let start = Instant::now();
+        self.stack.push(1);
+        spin_work(cs_work);
+        self.stack.pop().expect("stack emptied below its own push");
+        Some(start.elapsed().as_nanos() as u64)
+    }
+
+    fn verify(&self, _ops: u64) {
+        assert!(self.stack.is_empty(), "stack lost or duplicated elements");
+    }
+}
+
+/// The MPMC queues the `queue`, `faaqueue` and `twolock-*` targets compare.
+trait BenchQueue: Sync {
+    fn push(&self, v: u64);
+    fn pop(&self) -> Option<u64>;
+    fn is_empty(&self) -> bool;
+}
+
+impl BenchQueue for Queue<u64> {
+    fn push(&self, v: u64) {
+        Queue::push(self, v)
+    }
+
+    fn pop(&self) -> Option<u64> {
+        Queue::pop(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        Queue::is_empty(self)
+    }
+}
+
+impl BenchQueue for FaaQueue<u64> {
+    fn push(&self, v: u64) {
+        FaaQueue::push(self, v)
+    }
+
+    fn pop(&self) -> Option<u64> {
+        FaaQueue::pop(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        FaaQueue::is_empty(self)
+    }
+}
+
+impl<L: RawLock> BenchQueue for TwoLockQueue<u64, L> {
+    fn push(&self, v: u64) {
+        TwoLockQueue::push(self, v)
+    }
+
+    fn pop(&self) -> Option<u64> {
+        TwoLockQueue::pop(self)
+    }
+
+    fn is_empty(&self) -> bool {
+        TwoLockQueue::is_empty(self)
+    }
+}
+
+struct QueueWorkload<Q> {
+    queue: Q,
+}
+
+impl<Q: BenchQueue> Workload for QueueWorkload<Q> {
+    fn op(&self, _thread: usize, cs_work: u32) -> Option<u64> {
+        let start = Instant::now();
+        self.queue.push(1);
+        spin_work(cs_work);
+        self.queue.pop().expect("queue emptied below its own push");
+        Some(start.elapsed().as_nanos() as u64)
+    }
+
+    fn verify(&self, _ops: u64) {
+        assert!(self.queue.is_empty(), "queue lost or duplicated elements");
+    }
+}
+
+/// A one-way channel between the two workers of a pipeline target.
+trait Pipe: Sync {
+    /// Called only from the producer; returns false if the element did not fit.
+    fn send(&self, v: u64) -> bool;
+
+    /// Called only from the consumer, or after both workers are done.
+    fn recv(&self) -> Option<u64>;
+}
+
+impl Pipe for Queue<u64> {
+    fn send(&self, v: u64) -> bool {
+        self.push(v);
+        true
+    }
+
+    fn recv(&self) -> Option<u64> {
+        self.pop()
+    }
+}
+
+struct SpscPipe {
+    producer: UnsafeCell<Producer<u64>>,
+    consumer: UnsafeCell<Consumer<u64>>,
+}
+
+// Each half is only touched by the one worker `Pipe` allows to touch it.
+unsafe impl Sync for SpscPipe {}
+
+impl Pipe for SpscPipe {
+    fn send(&self, v: u64) -> bool {
+        unsafe { (*self.producer.get()).push(v).is_ok() }
+    }
+
+    fn recv(&self) -> Option<u64> {
+        unsafe { (*self.consumer.get()).pop() }
+    }
+}
+
+/// Worker 0 sends a running count, worker 1 receives it and checks nothing was lost,
+/// duplicated or reordered on the way. Each op is one element through the pipe.
+struct PipeWorkload<P> {
+    pipe: P,
+    sent: UnsafeCell<u64>,
+    received: UnsafeCell<u64>,
+    /// `done[i]` is set once worker `i` has run its last op, so the other side stops waiting
+    /// on it.
+    done: [AtomicBool; 2],
+}
+
+unsafe impl<P: Pipe> Sync for PipeWorkload<P> {}
+
+impl<P: Pipe> PipeWorkload<P> {
+    fn new(pipe: P) -> Self {
+        Self {
+            pipe,
+            sent: UnsafeCell::new(0),
+            received: UnsafeCell::new(0),
+            done: [AtomicBool::new(false), AtomicBool::new(false)],
+        }
+    }
+
+    fn check(&self, v: u64) {
+        let received = unsafe { &mut *self.received.get() };
+        assert_eq!(v, *received, "pipe lost, duplicated or reordered elements");
+        *received += 1;
+    }
+}
+
+impl<P: Pipe> Workload for PipeWorkload<P> {
+    fn op(&self, thread: usize, cs_work: u32) -> Option<u64> {
+        let start = Instant::now();
+        if thread == 0 {
+            let sent = unsafe { &mut *self.sent.get() };
+            while !self.pipe.send(*sent) {
+                if self.done[1].load(Ordering::Relaxed) {
+                    return None;
+                }
+                hint::spin_loop();
+            }
+            *sent += 1;
+        } else {
+            loop {
+                // Read before trying, so an empty pipe after the close really is the end.
+                let closed = self.done[0].load(Ordering::Acquire);
+                if let Some(v) = self.pipe.recv() {
+                    self.check(v);
+                    break;
+                }
+                if closed {
+                    return None;
+                }
+                hint::spin_loop();
+            }
+        }
+        spin_work(cs_work);
+        Some(start.elapsed().as_nanos() as u64)
+    }
+
+    fn finish(&self, thread: usize) {
+        self.done[thread].store(true, Ordering::Release);
+    }
+
+    fn verify(&self, _ops: u64) {
+        // A timed run can stop the consumer with elements still in flight.
+        while let Some(v) = self.pipe.recv() {
+            self.check(v);
+        }
+        let (sent, received) = unsafe { (*self.sent.get(), *self.received.get()) };
+        assert_eq!(sent, received, "pipe lost elements");
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub name: &'static str,
+    pub threads: usize,
+    pub placement: String,
+    /// CPU each worker was pinned to, in thread order; empty when the OS placed them.
+    pub cpus: Vec<usize>,
+    pub ops: u64,
+    pub elapsed: Duration,
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+    pub min_thread_ops: u64,
+    pub max_thread_ops: u64,
+    /// Jain's fairness index over per-thread op counts: 1.0 when every thread got the same
+    /// share, 1/threads when one thread did all the work.
+    pub fairness: f64,
+}
+
+impl Report {
+    pub fn throughput(&self) -> f64 {
+        self.ops as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+fn run<W: Workload>(
+    name: &'static str,
+    workload: &W,
+    threads: usize,
+    cpus: Option<Vec<usize>>,
+    cfg: &Config,
+) -> io::Result<Report> {
+    let barrier = Barrier::new(threads + 1);
+    let stop = AtomicBool::new(false);
+    let results = thread::scope(|s| {
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let cpu = cpus.as_ref().map(|c| c[i]);
+                let (barrier, stop) = (&barrier, &stop);
+                s.spawn(move || {
+                    let mut hist = Histogram::new();
+                    let mut ops = 0;
+                    barrier.wait();
+                    // Pin past the barrier, and finish on failure, so a worker that cannot be
+                    // pinned never leaves the others waiting for it.
+                    if let Some(cpu) = cpu
+                        && let Err(e) = affinity::pin_current(cpu)
+                    {
+                        workload.finish(i);
+                        return Err(io::Error::new(
+                            e.kind(),
+                            format!("failed to pin to cpu {cpu}: {e}"),
+                        ));
+                    }
+                    let start = Instant::now();
+                    while match cfg.duration {
+                        Some(_) => !stop.load(Ordering::Relaxed),
+                        None => ops < cfg.iters,
+                    } {
+                        let Some(waited) = workload.op(i, cfg.cs_work) else {
+                            break;
+                        };
+                        hist.record(waited);
+                        spin_work(cfg.ncs_work);
+                        ops += 1;
+                    }
+                    workload.finish(i);
+                    Ok((start, Instant::now(), ops, hist))
+                })
+            })
+            .collect();
+        barrier.wait();
+        if let Some(d) = cfg.duration {
+            thread::sleep(d);
+            stop.store(true, Ordering::Relaxed);
+        }
+        handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<io::Result<Vec<_>>>()
+    })?;
+
+    // Workers time themselves: the coordinating thread may not be scheduled again until long
+    // after they have started, especially when threads outnumber CPUs.
+    let start = results.iter().map(|r| r.0).min().unwrap();
+    let end = results.iter().map(|r| r.1).max().unwrap();
+    let elapsed = end - start;
+
+    let mut hist = Histogram::new();
+    for (_, _, _, h) in &results {
+        hist.merge(h);
+    }
+    let per_thread: Vec<u64> = results.iter().map(|r| r.2).collect();
+    let ops: u64 = per_thread.iter().sum();
+    workload.verify(ops);
+    let sum_sq: f64 = per_thread.iter().map(|&o| (o as f64).powi(2)).sum();
+    Ok(Report {
+        name,
+        threads,
+        placement: cfg.placement.to_string(),
+        cpus: cpus.unwrap_or_default(),
+        ops,
+        elapsed,
+        p50_ns: hist.percentile(0.50),
+        p90_ns: hist.percentile(0.90),
+        p99_ns: hist.percentile(0.99),
+        p999_ns: hist.percentile(0.999),
+        max_ns: hist.max,
+        min_thread_ops: per_thread.iter().copied().min().unwrap_or(0),
+        max_thread_ops: per_thread.iter().copied().max().unwrap_or(0),
+        fairness: if sum_sq == 0.0 {
+            1.0
+        } else {
+            (ops as f64).powi(2) / (threads as f64 * sum_sq)
+        },
+    })
+}
+
+fn lock_workload<L: RawLock>() -> LockWorkload<L> {
+    LockWorkload {
+        lock: L::default(),
+        counter: UnsafeCell::new(0),
+    }
+}
+
+fn run_twolock<L: RawLock>(
+    name: &'static str,
+    threads: usize,
+    cpus: Option<Vec<usize>>,
+    cfg: &Config,
+) -> io::Result<Report> {
+    let workload = QueueWorkload {
+        queue: TwoLockQueue::<u64, L>::new(),
+    };
+    run(name, &workload, threads, cpus, cfg)
+}
+
+/// Runs every selected target at every thread count and returns one report per point.
+pub fn run_all(cfg: &Config) -> io::Result<Vec<Report>> {
+    let topology = match cfg.placement {
+        Placement::None => Topology { cpus: Vec::new() },
+        _ => Topology::detect()?,
+    };
+    if let Placement::List(list) = &cfg.placement
+        && let Some(cpu) = list
+            .iter()
+            .find(|&&id| !topology.cpus.iter().any(|c| c.id == id))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cpu {cpu} is not online"),
+        ));
+    }
+    let mut reports = Vec::new();
+    for &target in &cfg.targets {
+        for &threads in cfg.sweep(target) {
+            let cpus = topology.assign(&cfg.placement, threads);
+            reports.push(match target {
+                "ticket" => run(target, &lock_workload::<TicketLock>(), threads, cpus, cfg),
+                "clh" => run(target, &lock_workload::<Clhlock>(), threads, cpus, cfg),
+                "mcs" => run(target, &lock_workload::<McsLock>(), threads, cpus, cfg),
+                "mcspark" => run(target, &lock_workload::<McsParkLock>(), threads, cpus, cfg),
+                "stack" => {
+                    let workload = StackWorkload {
+                        stack: Stack::new(),
+                    };
+                    run(target, &workload, threads, cpus, cfg)
+                }
+                "elimstack" => {
+                    let workload = StackWorkload {
+                        stack: EliminationStack::new(),
+                    };
+                    run(target, &workload, threads, cpus, cfg)
+                }
+                "queue" => {
+                    let workload = QueueWorkload {
+                        queue: Queue::new(),
+                    };
+                    run(target, &workload, threads, cpus, cfg)
+                }
+                "faaqueue" => {
+                    let workload = QueueWorkload {
+                        queue: FaaQueue::new(),
+                    };
+                    run(target, &workload, threads, cpus, cfg)
+                }
+                "twolock-ticket" => run_twolock::<TicketLock>(target, threads, cpus, cfg),
+                "twolock-clh" => run_twolock::<Clhlock>(target, threads, cpus, cfg),
+                "twolock-mcs" => run_twolock::<McsLock>(target, threads, cpus, cfg),
+                "twolock-mcspark" => run_twolock::<McsParkLock>(target, threads, cpus, cfg),
+                "spsc" => {
+                    let (producer, consumer) = spsc::ring(PIPE_CAPACITY);
+                    let workload = PipeWorkload::new(SpscPipe {
+                        producer: UnsafeCell::new(producer),
+                        consumer: UnsafeCell::new(consumer),
+                    });
+                    run(target, &workload, threads, cpus, cfg)
+                }
+                "spsc-queue" => run(target, &PipeWorkload::new(Queue::new()), threads, cpus, cfg),
+                _ => unreachable!("targets are validated in Config::from_args"),
+            }?);
+        }
+    }
+    Ok(reports)
+}
+
+const COLUMNS: [&str; 15] = [
+    "name",
+    "threads",
+    "placement",
+    "cpus",
+    "ops",
+    "elapsed_ns",
+    "throughput_ops_s",
+    "p50_ns",
+    "p90_ns",
+    "p99_ns",
+    "p999_ns",
+    "max_ns",
+    "min_thread_ops",
+    "max_thread_ops",
+    "fairness",
+];
+
+fn fields(r: &Report) -> [String; 15] {
+    let cpus: Vec<String> = r.cpus.iter().map(|c| c.to_string()).collect();
+    [
+        r.name.to_string(),
+        r.threads.to_string(),
+        r.placement.clone(),
+        cpus.join(";"),
+        r.ops.to_string(),
+        r.elapsed.as_nanos().to_string(),
+        format!("{:.1}", r.throughput()),
+        r.p50_ns.to_string(),
+        r.p90_ns.to_string(),
+        r.p99_ns.to_string(),
+        r.p999_ns.to_string(),
+        r.max_ns.to_string(),
+        r.min_thread_ops.to_string(),
+        r.max_thread_ops.to_string(),
+        format!("{:.4}", r.fairness),
+    ]
+}
+
+pub fn render(reports: &[Report], format: Format) -> String {
+    let mut out = String::new();
+    match format {
+        Format::Csv => {
+            writeln!(out, "{}", COLUMNS.join(",")).unwrap();
+            for r in reports {
+                // Only an explicit CPU list (`0-3,8`) carries commas; quote it whole.
+                let row: Vec<String> = fields(r)
+                    .into_iter()
+                    .map(|f| {
+                        if f.contains(',') {
+                            format!("\"{f}\"")
+                        } else {
+                            f
+                        }
+                    })
+                    .collect();
+                writeln!(out, "{}", row.join(",")).unwrap();
+            }
+        }
+        Format::Json => {
+            out.push_str("[\n");
+            for (i, r) in reports.iter().enumerate() {
+                let body: Vec<String> = COLUMNS
+                    .iter()
+                    .zip(fields(r))
+                    .map(|(k, v)| match *k {
+                        "name" | "placement" => format!("\"{k}\": \"{v}\""),
+                        "cpus" => format!("\"{k}\": [{}]", v.replace(';', ", ")),
+                        _ => format!("\"{k}\": {v}"),
+                    })
+                    .collect();
+                let sep = if i + 1 < reports.len() { "," } else { "" };
+                writeln!(out, "  {{{}}}{sep}", body.join(", ")).unwrap();
+            }
+            out.push_str("]\n");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(args: &str) -> Result<Config, String> {
+        Config::from_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn from_args_defaults_and_options() {
+        let cfg = config("mcs").unwrap();
+        assert_eq!(cfg.targets, ["mcs"]);
+        assert_eq!(cfg.threads, None);
+        assert_eq!((cfg.iters, cfg.duration), (100_000, None));
+        assert_eq!((cfg.cs_work, cfg.ncs_work), (0, 0));
+        assert_eq!(cfg.format, Format::Csv);
+        assert_eq!(cfg.placement, Placement::None);
+        assert_eq!(config("all").unwrap().targets, TARGETS);
+
+        let cfg = config("queue --threads 1,3 --iters 50 --cs 7 --ncs 9 --format json --pin 0-1,4")
+            .unwrap();
+        assert_eq!(cfg.threads, Some(vec![1, 3]));
+        assert_eq!(cfg.iters, 50);
+        assert_eq!((cfg.cs_work, cfg.ncs_work), (7, 9));
+        assert_eq!(cfg.format, Format::Json);
+        assert_eq!(cfg.placement, Placement::List(vec![0, 1, 4]));
+    }
+
+    #[test]
+    fn from_args_duration_and_iters_are_separate() {
+        // A duration leaves the op count alone; `run` checks the duration first.
+        let cfg = config("ticket --duration-ms 250").unwrap();
+        assert_eq!(cfg.duration, Some(Duration::from_millis(250)));
+        assert_eq!(cfg.iters, 100_000);
+        let cfg = config("ticket --iters 10").unwrap();
+        assert_eq!((cfg.iters, cfg.duration), (10, None));
+        let cfg = config("ticket --iters 10 --duration-ms 5").unwrap();
+        assert_eq!(
+            (cfg.iters, cfg.duration),
+            (10, Some(Duration::from_millis(5)))
+        );
+    }
+
+    #[test]
+    fn from_args_errors() {
+        let err = |args| config(args).unwrap_err();
+        assert_eq!(err(""), "missing subcommand");
+        assert_eq!(err("spinlock"), "unknown subcommand `spinlock`");
+        assert_eq!(err("clh --verbose 1"), "unknown option `--verbose`");
+        assert_eq!(err("clh --iters"), "missing value for `--iters`");
+        assert_eq!(err("clh --iters ten"), "invalid value `ten` for `--iters`");
+        assert_eq!(
+            err("clh --duration-ms -5"),
+            "invalid value `-5` for `--duration-ms`"
+        );
+        assert_eq!(
+            err("clh --threads 2,x"),
+            "invalid value `x` for `--threads`"
+        );
+        assert_eq!(err("clh --threads 0,2"), "thread counts must be positive");
+        assert_eq!(err("clh --format xml"), "unknown format `xml`");
+        assert_eq!(err("clh --pin 3-1"), "invalid placement `3-1`");
+    }
+
+    #[test]
+    fn from_args_rejects_lists_shorter_than_a_run() {
+        assert_eq!(
+            config("clh --pin 0-2").unwrap_err(),
+            "`--pin` lists 3 CPUs but `--threads` asks for 8"
+        );
+        // The check waits for every option, whichever order they come in.
+        assert!(config("clh --pin 0-2 --threads 1,3").is_ok());
+        assert!(config("clh --threads 1,3 --pin 0-1").is_err());
+        assert_eq!(
+            config("spsc --pin 4 --threads 1").unwrap_err(),
+            "`--pin` lists 1 CPUs but `spsc` runs 2"
+        );
+        assert!(config("spsc --pin 4,6").is_ok());
+        assert_eq!(
+            config("faaqueue --pin 0-7").unwrap_err(),
+            "`--pin` lists 8 CPUs but `faaqueue` sweeps up to 64"
+        );
+    }
+
+    #[test]
+    fn sweep_defaults_per_target() {
+        let cfg = config("all").unwrap();
+        assert_eq!(cfg.sweep("mcs"), [1, 2, 4, 8]);
+        assert_eq!(cfg.sweep("queue"), [1, 2, 4, 8, 16, 32, 64]);
+        assert_eq!(cfg.sweep("faaqueue"), [1, 2, 4, 8, 16, 32, 64]);
+        assert_eq!(cfg.sweep("spsc"), [2]);
+        let cfg = config("all --threads 3").unwrap();
+        assert_eq!(cfg.sweep("mcs"), [3]);
+        assert_eq!(cfg.sweep("faaqueue"), [3]);
+        assert_eq!(cfg.sweep("spsc"), [2]);
+    }
+
+    #[test]
+    fn percentile_edge_cases() {
+        let empty = Histogram::new();
+        for p in [0.0, 0.5, 1.0] {
+            assert_eq!(empty.percentile(p), 0);
+        }
+
+        let mut single = Histogram::new();
+        single.record(1000);
+        for p in [0.0, 0.5, 0.999, 1.0] {
+            assert_eq!(single.percentile(p), 1000);
+        }
+
+        let mut hist = Histogram::new();
+        (1..=1000).for_each(|v| hist.record(v));
+        assert_eq!(hist.percentile(0.0), 1);
+        assert_eq!(hist.percentile(1.0), 1000);
+        // Anything in between is reported at the bottom of its bucket, within ~6%.
+        let p50 = hist.percentile(0.5);
+        assert!((470..=500).contains(&p50), "p50 = {p50}");
+        let p99 = hist.percentile(0.99);
+        assert!((930..=990).contains(&p99), "p99 = {p99}");
+    }
+}
@@ -0,0 +1,310 @@
+use std::{error, fmt, sync::Arc};
+
+use crate::sync::{
+    Condvar, Mutex, RwLock,
+    atomic::{AtomicUsize, Ordering},
+};
+
+/// What a full ring does when the slowest subscriber has not read the oldest element yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Overwrite it: `send` never blocks, and a subscriber that falls a whole ring behind
+    /// gets [`RecvError::Lagged`] and resumes from the oldest element still held.
+    Lag,
+    /// Wait for it: `send` blocks until every subscriber has read the slot it is about to
+    /// reuse, so no subscriber ever misses an element.
+    Backpressure,
+}
+
+struct Slot<T> {
+    /// Subscribers that have yet to read this slot's element; only tracked under
+    /// [`Mode::Backpressure`].
+    rem: AtomicUsize,
+    inner: RwLock<Inner<T>>,
+}
+
+struct Inner<T> {
+    /// Position of the element held, one lap behind the slot's first position while empty.
+    pos: u64,
+    val: Option<T>,
+}
+
+struct Tail {
+    /// Position the next `send` writes.
+    pos: u64,
+    receivers: usize,
+    closed: bool,
+}
+
+struct Shared<T> {
+    buffer: Box<[Slot<T>]>,
+    mode: Mode,
+    /// Serializes senders, and guards the cursor bookkeeping of subscribing and leaving.
+    tail: Mutex<Tail>,
+    /// Signalled when an element is sent or the last sender leaves.
+    items: Condvar,
+    /// Signalled under [`Mode::Backpressure`] when a slot's last reader is done with it.
+    space: Condvar,
+    senders: AtomicUsize,
+}
+
+impl<T> Shared<T> {
+    fn slot(&self, pos: u64) -> &Slot<T> {
+        &self.buffer[(pos % self.buffer.len() as u64) as usize]
+    }
+
+    /// Marks `pos` as read by one more subscriber, waking a blocked sender on the last one.
+    fn release(&self, pos: u64) {
+        if self.mode == Mode::Backpressure && self.slot(pos).rem.fetch_sub(1, Ordering::AcqRel) == 1
+        {
+            let _tail = self.tail.lock().unwrap();
+            self.space.notify_all();
+        }
+    }
+}
+
+/// The sending half of a broadcast [`channel`]. Cloning it adds another producer; producers
+/// take turns on the tail.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// One subscriber of a broadcast [`channel`], with its own cursor into the ring: every
+/// subscriber sees every element sent after it subscribed, in order.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    next: u64,
+}
+
+/// Creates a broadcast channel over a ring of `capacity` slots, returning the first
+/// subscriber; more can join at any time through [`Sender::subscribe`].
+///
+/// Where the [`channel`](crate::channel) module hands each message to exactly one receiver,
+/// here each subscriber gets its own clone, so an element is only freed once its slot is
+/// reused.
+///
+/// # Panics
+///
+/// If `capacity` is zero.
+pub fn channel<T: Clone>(capacity: usize, mode: Mode) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "capacity must be non-zero");
+    let cap = capacity as u64;
+    let buffer = (0..cap)
+        .map(|i| Slot {
+            rem: AtomicUsize::new(0),
+            inner: RwLock::new(Inner {
+                pos: i.wrapping_sub(cap),
+                val: None,
+            }),
+        })
+        .collect();
+    let shared = Arc::new(Shared {
+        buffer,
+        mode,
+        tail: Mutex::new(Tail {
+            pos: 0,
+            receivers: 1,
+            closed: false,
+        }),
+        items: Condvar::new(),
+        space: Condvar::new(),
+        senders: AtomicUsize::new(1),
+    });
+    (
+        Sender {
+            shared: Arc::clone(&shared),
+        },
+        Receiver { shared, next: 0 },
+    )
+}
+
+/// Returned by [`Sender::send`] when there are no subscribers; carries the unsent element.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvError {
+    /// The subscriber fell behind by this many elements, which were overwritten; the next
+    /// receive returns the oldest element still held.
+    Lagged(u64),
+    /// Every sender is gone and the subscriber has seen everything they sent.
+    Closed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Lagged(u64),
+    Closed,
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("broadcasting with no subscribers")
+    }
+}
+
+impl<T> error::Error for SendError<T> {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Lagged(n) => write!(f, "subscriber lagged behind by {n} elements"),
+            RecvError::Closed => f.write_str("receiving on a closed broadcast channel"),
+        }
+    }
+}
+
+impl error::Error for RecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty broadcast channel"),
+            TryRecvError::Lagged(n) => RecvError::Lagged(*n).fmt(f),
+            TryRecvError::Closed => RecvError::Closed.fmt(f),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {}
+
+impl<T: Clone> Sender<T> {
+    /// Appends `t` for every current subscriber. Under [`Mode::Backpressure`] this blocks
+    /// while the slot to reuse still has readers.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        let shared = &*self.shared;
+        let mut tail = shared.tail.lock().unwrap();
+        let slot = shared.slot(tail.pos);
+        if shared.mode == Mode::Backpressure {
+            while tail.receivers > 0 && slot.rem.load(Ordering::Acquire) > 0 {
+                tail = shared.space.wait(tail).unwrap();
+            }
+        }
+        if tail.receivers == 0 {
+            return Err(SendError(t));
+        }
+
+        let mut inner = slot.inner.write().unwrap();
+        slot.rem.store(tail.receivers, Ordering::Relaxed);
+        inner.pos = tail.pos;
+        // The overwritten element, if any, is dropped here, under the write lock.
+        inner.val = Some(t);
+        drop(inner);
+        tail.pos += 1;
+        drop(tail);
+        shared.items.notify_all();
+        Ok(())
+    }
+
+    /// Adds a subscriber that sees everything sent from now on.
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut tail = self.shared.tail.lock().unwrap();
+        tail.receivers += 1;
+        Receiver {
+            shared: Arc::clone(&self.shared),
+            next: tail.pos,
+        }
+    }
+
+    pub fn receiver_count(&self) -> usize {
+        self.shared.tail.lock().unwrap().receivers
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.tail.lock().unwrap().closed = true;
+            self.shared.items.notify_all();
+        }
+    }
+}
+
+impl<T: Clone> Receiver<T> {
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let shared = &*self.shared;
+        loop {
+            let slot = shared.slot(self.next);
+            let inner = slot.inner.read().unwrap();
+            if inner.pos == self.next {
+                let t = inner.val.clone().expect("written slot is empty");
+                // Never take the tail lock while holding a slot: senders lock the other way.
+                drop(inner);
+                shared.release(self.next);
+                self.next += 1;
+                return Ok(t);
+            }
+            drop(inner);
+
+            // Either nothing was sent at `next` yet, or the slot has since been overwritten.
+            let tail = shared.tail.lock().unwrap();
+            if self.next >= tail.pos {
+                return Err(if tail.closed {
+                    TryRecvError::Closed
+                } else {
+                    TryRecvError::Empty
+                });
+            }
+            let oldest = tail.pos.saturating_sub(shared.buffer.len() as u64);
+            if self.next < oldest {
+                let missed = oldest - self.next;
+                self.next = oldest;
+                return Err(TryRecvError::Lagged(missed));
+            }
+            // Sent between our two looks; read it again.
+        }
+    }
+
+    /// Blocks until the next element arrives or every sender has been dropped.
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        loop {
+            match self.try_recv() {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Empty) => {}
+            }
+            let shared = &*self.shared;
+            let mut tail = shared.tail.lock().unwrap();
+            while self.next >= tail.pos && !tail.closed {
+                tail = shared.items.wait(tail).unwrap();
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let shared = &*self.shared;
+        let mut tail = shared.tail.lock().unwrap();
+        tail.receivers -= 1;
+        if shared.mode == Mode::Backpressure {
+            // Give up our claim on everything we never read, so senders are not kept waiting.
+            for pos in self.next..tail.pos {
+                if shared.slot(pos).rem.fetch_sub(1, Ordering::AcqRel) == 1 {
+                    shared.space.notify_all();
+                }
+            }
+        }
+        // A sender waiting for space also gives up once the last subscriber is gone.
+        if tail.receivers == 0 {
+            shared.space.notify_all();
+        }
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    error, fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    msqueue::Queue,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+struct Chan<T> {
+    queue: Queue<T>,
+    senders: AtomicUsize,
+    receivers: AtomicUsize,
+}
+
+impl<T> Chan<T> {
+    fn disconnected(&self) -> bool {
+        self.senders.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// The sending half of an [`unbounded`] channel. Cloning it adds another producer.
+pub struct Sender<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// The receiving half of an [`unbounded`] channel. Cloning it adds another consumer; each
+/// message goes to exactly one of them.
+pub struct Receiver<T> {
+    chan: Arc<Chan<T>>,
+}
+
+/// Creates an unbounded multi-producer multi-consumer channel backed by the Michael–Scott
+/// [`Queue`].
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Chan {
+        queue: Queue::new(),
+        senders: AtomicUsize::new(1),
+        receivers: AtomicUsize::new(1),
+    });
+    (
+        Sender {
+            chan: Arc::clone(&chan),
+        },
+        Receiver { chan },
+    )
+}
+
+/// Returned by [`Sender::send`] when every receiver is gone; carries the unsent message.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+/// Returned by [`Receiver::recv`] when the channel is empty and every sender is gone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TryRecvError {
+    Empty,
+    Disconnected,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecvTimeoutError {
+    Timeout,
+    Disconnected,
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a disconnected channel")
+    }
+}
+
+impl<T> error::Error for SendError<T> {}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on an empty and disconnected channel")
+    }
+}
+
+impl error::Error for RecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => f.write_str("receiving on an empty channel"),
+            TryRecvError::Disconnected => RecvError.fmt(f),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => f.write_str("timed out waiting on a channel"),
+            RecvTimeoutError::Disconnected => RecvError.fmt(f),
+        }
+    }
+}
+
+impl error::Error for RecvTimeoutError {}
+
+impl<T> Sender<T> {
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        if self.chan.receivers.load(Ordering::Acquire) == 0 {
+            return Err(SendError(t));
+        }
+        self.chan.queue.push(t);
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.senders.fetch_add(1, Ordering::Relaxed);
+        Self {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.chan.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.chan.queue.wake_all();
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Blocks until a message arrives or every sender has been dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.chan
+            .queue
+            .pop_until(None, || self.chan.disconnected())
+            .ok_or(RecvError)
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        if let Some(t) = self.chan.queue.pop() {
+            return Ok(t);
+        }
+        if !self.chan.disconnected() {
+            return Err(TryRecvError::Empty);
+        }
+        // Messages sent before the last sender left are visible once we have seen it leave.
+        self.chan.queue.pop().ok_or(TryRecvError::Disconnected)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        match self
+            .chan
+            .queue
+            .pop_until(Some(deadline), || self.chan.disconnected())
+        {
+            Some(t) => Ok(t),
+            None if self.chan.disconnected() => Err(RecvTimeoutError::Disconnected),
+            None => Err(RecvTimeoutError::Timeout),
+        }
+    }
+
+    /// Blocking iterator that ends once the channel is empty and disconnected.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    /// Iterator over the messages available right now, without blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+
+    pub(crate) fn queue(&self) -> &Queue<T> {
+        &self.chan.queue
+    }
+
+    pub(crate) fn is_disconnected(&self) -> bool {
+        self.chan.disconnected()
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.receivers.fetch_add(1, Ordering::Relaxed);
+        Self {
+            chan: Arc::clone(&self.chan),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.chan.receivers.fetch_sub(1, Ordering::Release);
+    }
+}
+
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+pub struct TryIter<'a, T> {
+    receiver: &'a Receiver<T>,
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+pub struct IntoIter<T> {
+    receiver: Receiver<T>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { receiver: self }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn send_fails_once_every_receiver_is_gone() {
+        let (s, r) = unbounded();
+        let r2 = r.clone();
+        drop(r);
+        assert_eq!(s.send(1), Ok(()));
+        assert_eq!(r2.recv(), Ok(1));
+        drop(r2);
+        assert_eq!(s.send(2), Err(SendError(2)));
+    }
+
+    #[test]
+    fn recv_drains_before_reporting_disconnect() {
+        let (s, r) = unbounded();
+        let s2 = s.clone();
+        s.send(1).unwrap();
+        s2.send(2).unwrap();
+        drop(s);
+        assert_eq!(r.recv(), Ok(1));
+        drop(s2);
+        assert_eq!(r.recv(), Ok(2));
+        assert_eq!(r.recv(), Err(RecvError));
+
+        // A receiver blocked on an empty channel wakes up when the last sender leaves.
+        let (s, r) = unbounded::<i32>();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| r.recv());
+            thread::sleep(Duration::from_millis(20));
+            drop(s);
+            assert_eq!(waiter.join().unwrap(), Err(RecvError));
+        });
+    }
+
+    #[test]
+    fn try_recv_tells_empty_from_disconnected() {
+        let (s, r) = unbounded();
+        assert_eq!(r.try_recv(), Err(TryRecvError::Empty));
+        s.send(1).unwrap();
+        drop(s);
+        assert_eq!(r.try_recv(), Ok(1));
+        assert_eq!(r.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout_tells_timeout_from_disconnected() {
+        let (s, r) = unbounded();
+        let start = Instant::now();
+        assert_eq!(
+            r.recv_timeout(Duration::from_millis(30)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        s.send(1).unwrap();
+        drop(s);
+        assert_eq!(r.recv_timeout(Duration::ZERO), Ok(1));
+        assert_eq!(
+            r.recv_timeout(Duration::from_secs(60)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+
+        // The last sender leaving cuts a timed wait short as well.
+        let (s, r) = unbounded::<i32>();
+        let start = Instant::now();
+        thread::scope(|scope| {
+            let waiter = scope.spawn(|| r.recv_timeout(Duration::from_secs(60)));
+            thread::sleep(Duration::from_millis(20));
+            drop(s);
+            assert_eq!(waiter.join().unwrap(), Err(RecvTimeoutError::Disconnected));
+        });
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn iterators_stop_where_they_should() {
+        let (s, r) = unbounded();
+        (0..3).for_each(|i| s.send(i).unwrap());
+        // Stops at the first empty poll, even with a sender still around.
+        assert_eq!(r.try_iter().collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(r.try_iter().next(), None);
+
+        // Blocks through the empty stretch and ends only once the last sender is gone.
+        let got: Vec<i32> = thread::scope(|scope| {
+            let consumer = scope.spawn(|| r.iter().collect());
+            for i in 3..6 {
+                thread::sleep(Duration::from_millis(5));
+                s.send(i).unwrap();
+            }
+            drop(s);
+            consumer.join().unwrap()
+        });
+        assert_eq!(got, [3, 4, 5]);
+
+        let (s, r) = unbounded();
+        (0..3).for_each(|i| s.send(i).unwrap());
+        drop(s);
+        let mut by_ref = Vec::new();
+        for i in &r {
+            by_ref.push(i);
+            if i == 1 {
+                break;
+            }
+        }
+        assert_eq!(by_ref, [0, 1]);
+        assert_eq!(r.into_iter().collect::<Vec<_>>(), [2]);
+    }
+}
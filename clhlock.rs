@@ -1,19 +1,18 @@
-use std::{
+use crossbeam_utils::CachePadded;
+
+use crate::{
+    rawlock::RawLock,
     sync::{
-        Arc,
-        atomic::{AtomicBool, AtomicPtr, AtomicUsize},
+        atomic::{AtomicBool, AtomicPtr, Ordering},
+        hint,
     },
-    thread::{self, JoinHandle},
-    time::Instant,
 };
 
-use crossbeam::utils::CachePadded;
-
 struct Node {
     locked: AtomicBool,
 }
 
-struct Clhlock {
+pub struct Clhlock {
     ptr: AtomicPtr<CachePadded<Node>>,
 }
 
@@ -25,7 +24,7 @@ impl Node {
     }
 }
 
-struct Token(*const CachePadded<Node>);
+pub struct Token(*const CachePadded<Node>);
 
 impl Clhlock {
     pub fn new() -> Self {
@@ -35,9 +34,11 @@ impl Clhlock {
     }
     pub fn lock(&self) -> Token {
         let node = Node::new(true);
-        let prev = self.ptr.swap(node, std::sync::atomic::Ordering::Relaxed);
-        while unsafe { (*prev).locked.load(std::sync::atomic::Ordering::Acquire) } {
-            std::hint::spin_loop();
+        // Release publishes our node's initialization to the next locker; Acquire pairs with
+        // the previous locker's swap so its node is initialized before we spin on it.
+        let prev = self.ptr.swap(node, Ordering::AcqRel);
+        while unsafe { (*prev).locked.load(Ordering::Acquire) } {
+            hint::spin_loop();
         }
         unsafe {
             drop(Box::from_raw(prev));
@@ -46,47 +47,34 @@ impl Clhlock {
     }
     pub fn unlock(&self, token: Token) {
         unsafe {
-            (*token.0)
-                .locked
-                .store(false, std::sync::atomic::Ordering::Release);
+            (*token.0).locked.store(false, Ordering::Release);
         }
     }
 }
 
 impl Drop for Clhlock {
     fn drop(&mut self) {
-        let node = self.ptr.get_mut();
+        let node = self.ptr.load(Ordering::Relaxed);
         unsafe {
             drop(Box::from_raw(node));
         }
     }
 }
 
-pub fn cllock() {
-    let a = Arc::new(Clhlock::new());
-    let counter = Arc::new(AtomicUsize::new(0));
-    let mut handles: Vec<JoinHandle<()>> = Vec::new();
-    for _ in 0..8 {
-        let a = Arc::clone(&a);
-        let counter = Arc::clone(&counter);
-        let handle = thread::spawn(move || {
-            for _ in 0..100 {
-                let d = a.lock();
-                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                a.unlock(d);
-            }
-        });
-        handles.push(handle);
+impl Default for Clhlock {
+    fn default() -> Self {
+        Self::new()
     }
-    let start = Instant::now();
-    for h in handles {
-        h.join().unwrap();
-    }
-    let duration = start.elapsed();
-    println!("Expexted:{:?} completed at {:?}", 8 * 100, duration);
-    println!(
-        "Actual Value:{:?}",
-        counter.load(std::sync::atomic::Ordering::Relaxed)
-    );
 }
 
+impl RawLock for Clhlock {
+    type Token = Token;
+
+    fn lock(&self) -> Token {
+        Clhlock::lock(self)
+    }
+
+    fn unlock(&self, token: Token) {
+        Clhlock::unlock(self, token)
+    }
+}
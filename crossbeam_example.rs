@@ -1,47 +1,40 @@
-// use std::{
-//     sync::{
-//         Arc,
-//         atomic::{AtomicBool, AtomicPtr, fence},
-//     },
-//     thread,
-// };
+use std::thread;
 
-// struct LinkedList {
-//     head: CachePadded<AtomicPtr<u32>>,
-//     tail: CachePadded<AtomicPtr<u32>>,
-// }
+use crate::channel::{TryRecvError, unbounded};
 
-// use crossbeam::{
-//     channel::{bounded, unbounded},
-//     utils::CachePadded,
-// };
-
-// pub fn cs() {
-//     let (s, r) = unbounded();
-//     let issent = Arc::new(AtomicBool::new(false));
-//     for i in 0..100 {
-//         let se = s.clone();
-//         let r = r.clone();
-//         let issent = Arc::clone(&issent);
-//         thread::scope(|s| {
-//             let sente = Arc::clone(&issent);
-//             s.spawn(move || {
-//                 let ka = issent.load(std::sync::atomic::Ordering::Acquire);
-//                 if ka {
-//                     std::hint::spin_loop();
-//                 }
-//                 se.send(i).unwrap();
-//                 issent.store(true, std::sync::atomic::Ordering::Release);
-//             });
-//             s.spawn(move || {
-//                 let ke = sente.load(std::sync::atomic::Ordering::Acquire);
-//                 if !ke {
-//                     std::hint::spin_loop();
-//                 }
-//                 let k = r.recv().unwrap();
-//                 sente.store(false, std::sync::atomic::Ordering::Release);
-//                 println!("{:?}", k);
-//             });
-//         })
-//     }
-// }
+/// Producers and consumers sharing one channel: every message reaches exactly one consumer,
+/// and the consumers' loops end once the last sender is gone and the queue is drained.
+pub fn cs() {
+    const PRODUCERS: usize = 4;
+    const CONSUMERS: usize = 2;
+    const N: usize = 100;
+    let (s, r) = unbounded();
+    let received: usize = thread::scope(|scope| {
+        for p in 0..PRODUCERS {
+            let s = s.clone();
+            scope.spawn(move || {
+                for i in 0..N {
+                    s.send(p * N + i).unwrap();
+                }
+            });
+        }
+        // From here on only the producers' clones keep the channel open.
+        drop(s);
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|c| {
+                let r = r.clone();
+                scope.spawn(move || {
+                    let mut count = 0;
+                    for k in r {
+                        println!("consumer {c}: {k}");
+                        count += 1;
+                    }
+                    count
+                })
+            })
+            .collect();
+        consumers.into_iter().map(|c| c.join().unwrap()).sum()
+    });
+    assert_eq!(received, PRODUCERS * N);
+    assert_eq!(r.try_recv(), Err(TryRecvError::Disconnected));
+}
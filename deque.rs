@@ -0,0 +1,295 @@
+use std::{
+    cell::{Cell, UnsafeCell},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr,
+    sync::Arc,
+};
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+use crossbeam_utils::CachePadded;
+
+use crate::sync::atomic::{AtomicIsize, Ordering, fence};
+
+const MIN_CAP: usize = 16;
+
+/// A power-of-two ring of slots indexed by the unbounded `top`/`bottom` positions.
+struct Buffer<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+impl<T> Buffer<T> {
+    fn new(cap: usize) -> Self {
+        debug_assert!(cap.is_power_of_two());
+        Self {
+            slots: (0..cap)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+        }
+    }
+
+    fn cap(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn at(&self, i: isize) -> *mut MaybeUninit<T> {
+        self.slots[i as usize & (self.cap() - 1)].get()
+    }
+
+    /// Reads slot `i` without taking ownership; the caller decides whether it won the element.
+    unsafe fn read(&self, i: isize) -> MaybeUninit<T> {
+        // Volatile: a thief may race with the owner reusing the slot a lap later, in which
+        // case its CAS on `top` fails and the torn value is discarded unread.
+        unsafe { ptr::read_volatile(self.at(i)) }
+    }
+
+    unsafe fn write(&self, i: isize, t: T) {
+        unsafe { ptr::write_volatile(self.at(i), MaybeUninit::new(t)) }
+    }
+}
+
+struct Inner<T> {
+    /// Next element thieves take; only ever advanced, by CAS.
+    top: CachePadded<AtomicIsize>,
+    /// One past the owner's end; only the owner writes it.
+    bottom: CachePadded<AtomicIsize>,
+    buffer: CachePadded<Atomic<Buffer<T>>>,
+}
+
+impl<T> Inner<T> {
+    fn len(&self) -> usize {
+        let top = self.top.load(Ordering::Acquire);
+        let bottom = self.bottom.load(Ordering::Acquire);
+        (bottom - top).max(0) as usize
+    }
+}
+
+impl<T> Drop for Inner<T> {
+    fn drop(&mut self) {
+        let top = self.top.load(Ordering::Relaxed);
+        let bottom = self.bottom.load(Ordering::Relaxed);
+        unsafe {
+            let buffer = self.buffer.load(Ordering::Relaxed, epoch::unprotected());
+            for i in top..bottom {
+                (*buffer.deref().at(i)).assume_init_drop();
+            }
+            drop(buffer.into_owned());
+        }
+    }
+}
+
+/// The owner's end of a [`deque`]: pushes and pops at the bottom, last in first out.
+pub struct Worker<T> {
+    inner: Arc<Inner<T>>,
+    /// Only the owning thread may push or pop.
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+/// A thief's handle on a [`deque`]: takes the oldest elements from the top. Clone it to hand
+/// to every thread that should be able to steal.
+pub struct Stealer<T> {
+    inner: Arc<Inner<T>>,
+}
+
+unsafe impl<T: Send> Send for Worker<T> {}
+unsafe impl<T: Send> Send for Stealer<T> {}
+unsafe impl<T: Send> Sync for Stealer<T> {}
+
+/// Result of a steal attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Steal<T> {
+    Empty,
+    Success(T),
+    /// Lost a race with another thief or the owner; the deque may still have elements.
+    Retry,
+}
+
+impl<T> Steal<T> {
+    pub fn success(self) -> Option<T> {
+        match self {
+            Steal::Success(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+/// Creates a Chase–Lev dynamic circular work-stealing deque.
+///
+/// The owner's `push` and `pop` need no atomic read-modify-write except when racing a thief
+/// for the last element. The buffer doubles when full; a replaced buffer may still be read by
+/// a thief that loaded it earlier, so it is retired through `crossbeam_epoch`.
+pub fn deque<T>() -> (Worker<T>, Stealer<T>) {
+    let inner = Arc::new(Inner {
+        top: CachePadded::new(AtomicIsize::new(0)),
+        bottom: CachePadded::new(AtomicIsize::new(0)),
+        buffer: CachePadded::new(Atomic::new(Buffer::new(MIN_CAP))),
+    });
+    (
+        Worker {
+            inner: Arc::clone(&inner),
+            _not_sync: PhantomData,
+        },
+        Stealer { inner },
+    )
+}
+
+impl<T> Worker<T> {
+    pub fn push(&self, t: T) {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Ordering::Relaxed);
+        let top = inner.top.load(Ordering::Acquire);
+        // Only the owner replaces the buffer, so it needs no protection to read it.
+        let mut buffer = unsafe { inner.buffer.load(Ordering::Relaxed, epoch::unprotected()) };
+        if bottom - top >= unsafe { buffer.deref() }.cap() as isize {
+            self.grow(top, bottom);
+            buffer = unsafe { inner.buffer.load(Ordering::Relaxed, epoch::unprotected()) };
+        }
+        unsafe { buffer.deref().write(bottom, t) };
+        // Publishes the element before thieves can see it below `bottom`.
+        fence(Ordering::Release);
+        inner.bottom.store(bottom + 1, Ordering::Relaxed);
+    }
+
+    /// Moves `top..bottom` into a buffer twice the size and retires the old one.
+    #[cold]
+    fn grow(&self, top: isize, bottom: isize) {
+        let inner = &*self.inner;
+        let guard = &epoch::pin();
+        let old = inner.buffer.load(Ordering::Relaxed, guard);
+        let new = Buffer::new(unsafe { old.deref() }.cap() * 2);
+        for i in top..bottom {
+            unsafe { ptr::copy_nonoverlapping(old.deref().at(i), new.at(i), 1) };
+        }
+        inner.buffer.store(Owned::new(new), Ordering::Release);
+        // The elements now live in the new buffer; dropping the old one frees only its slots.
+        unsafe { guard.defer_destroy(old) };
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let inner = &*self.inner;
+        let bottom = inner.bottom.load(Ordering::Relaxed) - 1;
+        let buffer = unsafe { inner.buffer.load(Ordering::Relaxed, epoch::unprotected()) };
+        inner.bottom.store(bottom, Ordering::Relaxed);
+        // Orders our claim on `bottom` before reading `top`, against the mirror-image fence
+        // in `steal`: at most one of us can miss the other.
+        fence(Ordering::SeqCst);
+        let top = inner.top.load(Ordering::Relaxed);
+        if top > bottom {
+            inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            return None;
+        }
+        let t = unsafe { buffer.deref().read(bottom) };
+        if top == bottom {
+            // Last element: settle the race with thieves through `top`, then restore `bottom`
+            // to the now-empty position either way.
+            let won = inner
+                .top
+                .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok();
+            inner.bottom.store(bottom + 1, Ordering::Relaxed);
+            if !won {
+                return None;
+            }
+        }
+        Some(unsafe { t.assume_init() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Creates another handle thieves can steal through.
+    pub fn stealer(&self) -> Stealer<T> {
+        Stealer {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Stealer<T> {
+    /// Takes the oldest element.
+    pub fn steal(&self) -> Steal<T> {
+        let inner = &*self.inner;
+        let top = inner.top.load(Ordering::Acquire);
+        fence(Ordering::SeqCst);
+        let bottom = inner.bottom.load(Ordering::Acquire);
+        if top >= bottom {
+            return Steal::Empty;
+        }
+        let guard = &epoch::pin();
+        let buffer = inner.buffer.load(Ordering::Acquire, guard);
+        let t = unsafe { buffer.deref().read(top) };
+        match inner
+            .top
+            .compare_exchange(top, top + 1, Ordering::SeqCst, Ordering::Relaxed)
+        {
+            Ok(_) => Steal::Success(unsafe { t.assume_init() }),
+            Err(_) => Steal::Retry,
+        }
+    }
+
+    /// Moves up to half of the elements, oldest first, into `dest` and returns how many.
+    ///
+    /// Elements are claimed one CAS at a time: the owner pops from the other end without a
+    /// CAS, so a single claim over a range could overlap elements it is taking.
+    pub fn steal_batch(&self, dest: &Worker<T>) -> Steal<usize> {
+        let want = self.inner.len().div_ceil(2);
+        let mut moved = 0;
+        while moved < want {
+            match self.steal() {
+                Steal::Success(t) => {
+                    dest.push(t);
+                    moved += 1;
+                }
+                Steal::Retry if moved == 0 => return Steal::Retry,
+                Steal::Empty | Steal::Retry => break,
+            }
+        }
+        if moved == 0 {
+            Steal::Empty
+        } else {
+            Steal::Success(moved)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Stealer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn growth_keeps_order_and_drops() {
+        let counts = DropCounts::new(1000);
+        let (worker, stealer) = deque();
+        for v in 0..1000 {
+            worker.push(counts.track(v));
+        }
+        assert_eq!(worker.len(), 1000);
+        assert!(matches!(stealer.steal(), Steal::Success(t) if t.value == 0));
+        assert_eq!(worker.pop().map(|t| t.value), Some(999));
+        // The remaining 998 are dropped with the deque, through the grown buffer.
+        drop((worker, stealer));
+        counts.assert_all_dropped();
+    }
+}
@@ -0,0 +1,188 @@
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared, pin};
+use crossbeam_utils::CachePadded;
+
+use crate::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        hint,
+    },
+    treiberstack::{Node, Stack},
+    util::random,
+};
+
+/// Slots in the elimination array. One under loom, so the slot picked is deterministic.
+#[cfg(not(loom))]
+const SLOTS: usize = 16;
+#[cfg(loom)]
+const SLOTS: usize = 1;
+
+/// How long an operation waits in its slot for a partner before going back to the stack.
+/// Short under loom, where every spin is a scheduling point.
+#[cfg(not(loom))]
+const SPINS: usize = 128;
+#[cfg(loom)]
+const SPINS: usize = 2;
+
+/// Treiber [`Stack`] with elimination backoff (Hendler, Shavit and Yerushalmi).
+///
+/// An operation whose CAS on `head` fails does not retry straight away: it visits a random
+/// slot of a small elimination array instead. A push leaves its node there for a while; a
+/// pop that finds it takes the element directly, and the pair completes without touching
+/// `head` at all, as if the push were immediately followed by the pop. Only the first
+/// `width` slots are used: collisions with another operation in the same slot widen the
+/// range, and waits that time out without a partner narrow it, so the array spreads out
+/// under heavy contention and stays dense enough for partners to meet under light.
+pub struct EliminationStack<T> {
+    stack: Stack<T>,
+    /// A node offered by a waiting push, or null.
+    slots: Box<[CachePadded<Atomic<Node<T>>>]>,
+    width: CachePadded<AtomicUsize>,
+}
+
+unsafe impl<T: Send> Send for EliminationStack<T> {}
+unsafe impl<T: Send> Sync for EliminationStack<T> {}
+
+impl<T> Default for EliminationStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> EliminationStack<T> {
+    pub fn new() -> Self {
+        Self {
+            stack: Stack::new(),
+            slots: (0..SLOTS)
+                .map(|_| CachePadded::new(Atomic::null()))
+                .collect(),
+            width: CachePadded::new(AtomicUsize::new(1)),
+        }
+    }
+
+    pub fn push(&self, t: T) {
+        let guard = &pin();
+        let mut node = Node::new(t);
+        loop {
+            node = match self.stack.try_push(node) {
+                Ok(()) => return,
+                Err(node) => node,
+            };
+            node = match self.offer(Owned::from(node), guard) {
+                Ok(()) => return,
+                Err(node) => node.into_box(),
+            };
+        }
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let guard = &pin();
+        loop {
+            if let Ok(t) = self.stack.try_pop(guard) {
+                return t;
+            }
+            if let Some(t) = self.take(guard) {
+                return Some(t);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Current number of elimination slots in use.
+    pub fn width(&self) -> usize {
+        self.width.load(Ordering::Relaxed)
+    }
+
+    fn slot(&self) -> &Atomic<Node<T>> {
+        &self.slots[random(self.width())]
+    }
+
+    /// Leaves `node` in a slot for a pop to take, handing it back if none came.
+    fn offer(&self, node: Owned<Node<T>>, guard: &Guard) -> Result<(), Owned<Node<T>>> {
+        let slot = self.slot();
+        let mine = match slot.compare_exchange(
+            Shared::null(),
+            node,
+            Ordering::Release,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            Ok(mine) => mine,
+            Err(e) => {
+                self.grow();
+                return Err(e.new);
+            }
+        };
+        for _ in 0..SPINS {
+            // The node cannot come back to this slot while we are pinned: whoever took it
+            // retired it through our epoch.
+            if slot.load(Ordering::Acquire, guard) != mine {
+                return Ok(());
+            }
+            hint::spin_loop();
+        }
+        match slot.compare_exchange(
+            mine,
+            Shared::null(),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            Ok(_) => {
+                self.shrink();
+                Err(unsafe { mine.into_owned() })
+            }
+            // Taken just before we withdrew it.
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Waits in a slot for a push's node and takes its element.
+    fn take(&self, guard: &Guard) -> Option<T> {
+        let slot = self.slot();
+        for _ in 0..SPINS {
+            let offered = slot.load(Ordering::Acquire, guard);
+            if !offered.is_null() {
+                if slot
+                    .compare_exchange(
+                        offered,
+                        Shared::null(),
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                        guard,
+                    )
+                    .is_err()
+                {
+                    // Another pop, or the push withdrawing: the slot is contended.
+                    self.grow();
+                    return None;
+                }
+                // The CAS made the node ours, but its push may still be comparing against it.
+                let t = unsafe { offered.deref().take() };
+                unsafe { guard.defer_destroy(offered) };
+                return Some(t);
+            }
+            hint::spin_loop();
+        }
+        self.shrink();
+        None
+    }
+
+    fn grow(&self) {
+        let _ = self
+            .width
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |w| {
+                (w < SLOTS).then_some(w + 1)
+            });
+    }
+
+    fn shrink(&self) {
+        let _ = self
+            .width
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |w| {
+                (w > 1).then_some(w - 1)
+            });
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    cell::UnsafeCell,
+    time::{Duration, Instant},
+};
+
+use crossbeam_epoch::{Atomic, Owned, Shared, pin};
+
+use crate::sync::{
+    atomic::{AtomicBool, Ordering},
+    hint, park_timeout,
+    thread::{self, Thread},
+};
+
+/// Spins on a published offer before parking, since a partner often arrives within a few
+/// hundred cycles. Short under loom, where every spin is a scheduling point.
+#[cfg(not(loom))]
+const SPINS: usize = 256;
+#[cfg(loom)]
+const SPINS: usize = 2;
+
+/// A value waiting in the slot for a partner.
+struct Offer<T> {
+    /// The waiter's value, taken by the partner that claims the offer.
+    item: UnsafeCell<Option<T>>,
+    /// The partner's value, left for the waiter.
+    reply: UnsafeCell<Option<T>>,
+    /// Set by the partner once `reply` is written; the waiter frees the offer after that.
+    matched: AtomicBool,
+    waiter: Thread,
+}
+
+/// Lets pairs of threads swap values: each [`exchange`](Exchanger::exchange) returns the value
+/// of the call it was paired with.
+///
+/// The first thread of a pair publishes an offer in a single slot with one CAS and waits; the
+/// second claims it by CAS-ing the slot back to null, which makes it the only thread that may
+/// touch the offer's values. Offers are retired through `crossbeam_epoch`, so a thread that
+/// read the slot just before the offer was claimed or withdrawn can still safely look at it,
+/// while the waiter itself parks unpinned.
+pub struct Exchanger<T> {
+    slot: Atomic<Offer<T>>,
+}
+
+unsafe impl<T: Send> Send for Exchanger<T> {}
+unsafe impl<T: Send> Sync for Exchanger<T> {}
+
+impl<T> Default for Exchanger<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Exchanger<T> {
+    pub fn new() -> Self {
+        Self {
+            slot: Atomic::null(),
+        }
+    }
+
+    /// Swaps `t` for the value of another thread's `exchange`, waiting at most `timeout` for
+    /// one to arrive. Gives `t` back if none did.
+    pub fn exchange(&self, t: T, timeout: Duration) -> Result<T, T> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut t = t;
+        loop {
+            let guard = &pin();
+            let offer = self.slot.load(Ordering::Acquire, guard);
+            if let Some(o) = unsafe { offer.as_ref() } {
+                // Someone is waiting: claim their offer and swap.
+                if self
+                    .slot
+                    .compare_exchange(
+                        offer,
+                        Shared::null(),
+                        Ordering::Acquire,
+                        Ordering::Relaxed,
+                        guard,
+                    )
+                    .is_ok()
+                {
+                    let theirs = unsafe { (*o.item.get()).take() }.expect("offer without item");
+                    unsafe { *o.reply.get() = Some(t) };
+                    // The waiter may free the offer as soon as it sees `matched`.
+                    let waiter = o.waiter.clone();
+                    o.matched.store(true, Ordering::Release);
+                    waiter.unpark();
+                    return Ok(theirs);
+                }
+                continue;
+            }
+            if deadline.is_some_and(|d| d <= Instant::now()) {
+                return Err(t);
+            }
+
+            let mine = Owned::new(Offer {
+                item: UnsafeCell::new(Some(t)),
+                reply: UnsafeCell::new(None),
+                matched: AtomicBool::new(false),
+                waiter: thread::current(),
+            });
+            match self.slot.compare_exchange(
+                Shared::null(),
+                mine,
+                Ordering::Release,
+                Ordering::Relaxed,
+                guard,
+            ) {
+                Ok(mine) => return self.wait(mine.as_raw(), deadline),
+                Err(e) => {
+                    t = unsafe { (*e.new.item.get()).take() }.expect("unpublished offer");
+                }
+            }
+        }
+    }
+
+    /// Waits for a partner to claim `mine`, withdrawing it once `deadline` passes.
+    fn wait(&self, mine: *const Offer<T>, deadline: Option<Instant>) -> Result<T, T> {
+        // Only this thread frees `mine`, so it stays valid here without a guard.
+        let offer = unsafe { &*mine };
+        let mut spins = 0;
+        while !offer.matched.load(Ordering::Acquire) {
+            if spins < SPINS {
+                spins += 1;
+                hint::spin_loop();
+                continue;
+            }
+            match deadline.map(|d| d.checked_duration_since(Instant::now())) {
+                None => thread::park(),
+                Some(Some(r)) if !r.is_zero() => park_timeout(r),
+                Some(_) => {
+                    let guard = &pin();
+                    if self
+                        .slot
+                        .compare_exchange(
+                            Shared::from(mine),
+                            Shared::null(),
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                            guard,
+                        )
+                        .is_ok()
+                    {
+                        let t = unsafe { (*offer.item.get()).take() }.expect("unclaimed offer");
+                        unsafe { guard.defer_destroy(Shared::from(mine)) };
+                        return Err(t);
+                    }
+                    // Claimed just now: the partner is about to set `matched`.
+                    while !offer.matched.load(Ordering::Acquire) {
+                        thread::yield_now();
+                    }
+                }
+            }
+        }
+        let t = unsafe { (*offer.reply.get()).take() }.expect("matched offer without reply");
+        unsafe { pin().defer_destroy(Shared::from(mine)) };
+        Ok(t)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn times_out_alone() {
+        let counts = DropCounts::new(1);
+        let exchanger = Exchanger::new();
+        let start = Instant::now();
+        let back = exchanger.exchange(counts.track(1), Duration::from_millis(5));
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        assert!(matches!(back, Err(t) if t.value == 1));
+        // The value came back out of the withdrawn offer, so nothing may drop it again.
+        counts.assert_all_dropped();
+    }
+}
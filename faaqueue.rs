@@ -0,0 +1,409 @@
+use std::{cell::UnsafeCell, mem::MaybeUninit};
+
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared, pin};
+use crossbeam_utils::CachePadded;
+
+use crate::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/// Elements one ring holds. Tiny under loom so the models reach a ring closing.
+#[cfg(not(loom))]
+const RING: usize = 1024;
+#[cfg(loom)]
+const RING: usize = 2;
+
+/// Entries in each index queue: twice the indices it can hold, which is what lets an
+/// enqueuer always find a usable entry without waiting on a slow dequeuer.
+const ENTRIES: u64 = 2 * RING as u64;
+/// Width of an entry's index field; a position's low bits pick its entry.
+const ORDER: u32 = ENTRIES.trailing_zeros();
+/// Index field of an entry that holds nothing; also the mask for that field.
+const EMPTY: u64 = ENTRIES - 1;
+/// ORed into a taken entry's index field, leaving it at `CONSUMED` or `EMPTY`: both are
+/// above every real index, so either reads as free.
+const CONSUMED: u64 = ENTRIES - 2;
+/// Cleared by a dequeuer that passes an entry still holding an earlier lap's index, so that
+/// an enqueuer behind the dequeuers does not fill it.
+const SAFE: u64 = 1 << ORDER;
+/// Set in an index queue's tail once it takes no more enqueues.
+const CLOSED: u64 = 1 << 63;
+/// Failed dequeues after the last enqueue beyond which the queue counts as empty; `3 *
+/// RING - 1` is enough for a dequeuer to reach any index still in it.
+const THRESHOLD: i64 = 3 * RING as i64 - 1;
+
+/// Lap of the entries that a head or tail position is on.
+fn lap(position: u64) -> u64 {
+    position >> ORDER
+}
+
+fn entry_lap(entry: u64) -> u64 {
+    entry >> (ORDER + 1)
+}
+
+/// A bounded queue of ring indices, Nikolaev's SCQ: positions come from `fetch_add` on
+/// `head` and `tail`, and each entry packs the lap it was written on next to the index, so
+/// a CAS on one word settles races between an enqueuer and a dequeuer on the same entry.
+struct IndexQueue {
+    head: CachePadded<AtomicU64>,
+    tail: CachePadded<AtomicU64>,
+    threshold: CachePadded<AtomicI64>,
+    entries: Box<[AtomicU64]>,
+}
+
+impl IndexQueue {
+    fn empty() -> Self {
+        Self {
+            // Start on lap 1 so that the lap-0 entries read as free.
+            head: CachePadded::new(AtomicU64::new(ENTRIES)),
+            tail: CachePadded::new(AtomicU64::new(ENTRIES)),
+            threshold: CachePadded::new(AtomicI64::new(-1)),
+            entries: (0..ENTRIES).map(|_| AtomicU64::new(SAFE | EMPTY)).collect(),
+        }
+    }
+
+    /// Holding every index, `0..RING`, in order.
+    fn full() -> Self {
+        let queue = Self::empty();
+        for index in 0..RING as u64 {
+            queue.enqueue(index);
+        }
+        queue
+    }
+
+    /// Returns `false`, leaving `index` with the caller, if the queue has been closed.
+    fn enqueue(&self, index: u64) -> bool {
+        loop {
+            let t = self.tail.fetch_add(1, Ordering::SeqCst);
+            if t & CLOSED != 0 {
+                return false;
+            }
+            let entry = &self.entries[(t % ENTRIES) as usize];
+            let mut e = entry.load(Ordering::SeqCst);
+            while entry_lap(e) < lap(t)
+                && e & EMPTY >= CONSUMED
+                && (e & SAFE != 0 || self.head.load(Ordering::SeqCst) <= t)
+            {
+                let new = lap(t) << (ORDER + 1) | SAFE | index;
+                match entry.compare_exchange(e, new, Ordering::SeqCst, Ordering::SeqCst) {
+                    Ok(_) => {
+                        if self.threshold.load(Ordering::SeqCst) != THRESHOLD {
+                            self.threshold.store(THRESHOLD, Ordering::SeqCst);
+                        }
+                        return true;
+                    }
+                    Err(current) => e = current,
+                }
+            }
+            // A dequeuer got here first or the entry is still in use; take the next position.
+        }
+    }
+
+    /// With `draining` set, keeps going until `head` passes `tail` instead of giving up once
+    /// the threshold runs out. Only worth it on a closed queue, whose `tail` has stopped.
+    fn dequeue(&self, draining: bool) -> Option<u64> {
+        if !draining && self.threshold.load(Ordering::SeqCst) < 0 {
+            return None;
+        }
+        loop {
+            let h = self.head.fetch_add(1, Ordering::SeqCst);
+            let entry = &self.entries[(h % ENTRIES) as usize];
+            let mut e = entry.load(Ordering::SeqCst);
+            loop {
+                if entry_lap(e) == lap(h) {
+                    entry.fetch_or(CONSUMED, Ordering::SeqCst);
+                    return Some(e & EMPTY);
+                }
+                if entry_lap(e) > lap(h) {
+                    break;
+                }
+                // Move a free entry on to our lap so that the enqueuer of `h`, if it is still
+                // coming, fails and retries; an entry still in use is only marked unsafe.
+                let new = if e & EMPTY >= CONSUMED {
+                    lap(h) << (ORDER + 1) | e & SAFE | EMPTY
+                } else {
+                    e & !SAFE
+                };
+                match entry.compare_exchange(e, new, Ordering::SeqCst, Ordering::SeqCst) {
+                    Ok(_) => break,
+                    Err(current) => e = current,
+                }
+            }
+
+            let t = self.tail.load(Ordering::SeqCst);
+            if t & !CLOSED <= h + 1 {
+                self.catch_up(t, h + 1);
+                self.threshold.fetch_sub(1, Ordering::SeqCst);
+                return None;
+            }
+            if self.threshold.fetch_sub(1, Ordering::SeqCst) <= 0 && !draining {
+                return None;
+            }
+        }
+    }
+
+    /// Pulls `tail` up to `head` once dequeuers have overshot it, so that enqueuers do not
+    /// land on entries they already passed.
+    fn catch_up(&self, mut tail: u64, mut head: u64) {
+        while let Err(current) = self.tail.compare_exchange(
+            tail,
+            head | tail & CLOSED,
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        ) {
+            tail = current;
+            head = self.head.load(Ordering::SeqCst);
+            if tail & !CLOSED >= head {
+                break;
+            }
+        }
+    }
+
+    fn close(&self) {
+        self.tail.fetch_or(CLOSED, Ordering::SeqCst);
+    }
+
+    /// Looks at the entries rather than the counters, which move on failed attempts too.
+    fn is_empty(&self) -> bool {
+        self.entries
+            .iter()
+            .all(|e| e.load(Ordering::SeqCst) & EMPTY >= CONSUMED)
+    }
+}
+
+/// `RING` slots for elements, with their indices cycling between the `free` and `full`
+/// index queues: a push moves an index from `free` to `full`, a pop moves it back.
+struct Ring<T> {
+    full: IndexQueue,
+    free: IndexQueue,
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    next: Atomic<Ring<T>>,
+}
+
+impl<T> Ring<T> {
+    fn new() -> Self {
+        Self {
+            full: IndexQueue::empty(),
+            free: IndexQueue::full(),
+            slots: (0..RING)
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            next: Atomic::null(),
+        }
+    }
+
+    /// Hands `t` back once the ring is closed, closing it first if every slot is taken.
+    fn push(&self, t: T) -> Result<(), T> {
+        let Some(index) = self.free.dequeue(false) else {
+            self.full.close();
+            return Err(t);
+        };
+        let slot = self.slots[index as usize].get();
+        unsafe { (*slot).write(t) };
+        if self.full.enqueue(index) {
+            return Ok(());
+        }
+        // Closed since we took the index, and no pop can reach the slot: take `t` back.
+        let t = unsafe { (*slot).assume_init_read() };
+        self.free.enqueue(index);
+        Err(t)
+    }
+
+    fn pop(&self, draining: bool) -> Option<T> {
+        let index = self.full.dequeue(draining)?;
+        let t = unsafe { (*self.slots[index as usize].get()).assume_init_read() };
+        self.free.enqueue(index);
+        Some(t)
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop(true).is_some() {}
+    }
+}
+
+/// Unbounded MPMC queue of linked rings whose entries are handed out by fetch-and-add:
+/// Nikolaev's LSCQ, the portable relative of LCRQ that needs only single-word CAS.
+///
+/// Where [`Queue`](crate::msqueue::Queue) makes every producer CAS the same tail pointer and
+/// retry on contention, here each producer and consumer claims its own entry with one
+/// `fetch_add` that always succeeds, so contention costs a cache-line transfer rather than a
+/// retry loop; CAS is left for settling an entry between an enqueuer and a dequeuer that
+/// raced ahead of it, and for linking rings. A ring is reused lap after lap for as long as
+/// it has room, so a queue that stays under `RING` elements allocates nothing after
+/// construction. Only when a push finds it full is the ring closed and a fresh one linked
+/// behind it; the closed ring is retired through `crossbeam_epoch` once popped empty.
+pub struct FaaQueue<T> {
+    head: CachePadded<Atomic<Ring<T>>>,
+    tail: CachePadded<Atomic<Ring<T>>>,
+}
+
+unsafe impl<T> Sync for FaaQueue<T> where T: Send {}
+unsafe impl<T> Send for FaaQueue<T> where T: Send {}
+
+impl<T> Default for FaaQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> FaaQueue<T> {
+    pub fn new() -> Self {
+        let ring = Owned::new(Ring::new()).into_shared(unsafe { crossbeam_epoch::unprotected() });
+        Self {
+            head: CachePadded::new(Atomic::from(ring)),
+            tail: CachePadded::new(Atomic::from(ring)),
+        }
+    }
+
+    pub fn push(&self, t: T) {
+        self.push_with_guard(t, &pin());
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        self.pop_with_guard(&pin())
+    }
+
+    pub fn push_with_guard(&self, mut t: T, guard: &Guard) {
+        loop {
+            let tail = self.tail.load(Ordering::Acquire, guard);
+            let ring = unsafe { tail.deref() };
+            let next = ring.next.load(Ordering::Acquire, guard);
+            if !next.is_null() {
+                let _ = self.tail.compare_exchange(
+                    tail,
+                    next,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                );
+                continue;
+            }
+            t = match ring.push(t) {
+                Ok(()) => return,
+                Err(t) => t,
+            };
+
+            // This ring is closed: link a new one holding `t`, unless someone beat us to it.
+            let new = Ring::new();
+            let Ok(()) = new.push(t) else {
+                unreachable!("a fresh ring has room")
+            };
+            match ring.next.compare_exchange(
+                Shared::null(),
+                Owned::new(new),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(new) => {
+                    let _ = self.tail.compare_exchange(
+                        tail,
+                        new,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        guard,
+                    );
+                    return;
+                }
+                Err(e) => t = e.new.pop(false).expect("the unused ring holds `t`"),
+            }
+        }
+    }
+
+    pub fn pop_with_guard(&self, guard: &Guard) -> Option<T> {
+        loop {
+            let head = self.head.load(Ordering::Acquire, guard);
+            let ring = unsafe { head.deref() };
+            if let Some(t) = ring.pop(false) {
+                return Some(t);
+            }
+            let next = ring.next.load(Ordering::Acquire, guard);
+            if next.is_null() {
+                return None;
+            }
+
+            // The ring is closed, but pushes that took a position before it closed may still
+            // be landing. Draining claims every position up to `tail` for some pop, so any
+            // such push either fails and moves on or is taken by the pop holding its position.
+            if let Some(t) = ring.pop(true) {
+                return Some(t);
+            }
+            // Never let `tail` lag behind on a ring we are about to retire.
+            let _ =
+                self.tail
+                    .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed, guard);
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(head) };
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let guard = pin();
+        let mut ring = self.head.load(Ordering::Acquire, &guard);
+        while let Some(r) = unsafe { ring.as_ref() } {
+            if !r.full.is_empty() {
+                return false;
+            }
+            ring = r.next.load(Ordering::Acquire, &guard);
+        }
+        true
+    }
+}
+
+impl<T> Drop for FaaQueue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = crossbeam_epoch::unprotected();
+            let mut ring = self.head.load(Ordering::Relaxed, guard);
+            while !ring.is_null() {
+                let next = ring.deref().next.load(Ordering::Relaxed, guard);
+                drop(ring.into_owned());
+                ring = next;
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn drop_drops_what_is_left() {
+        let counts = DropCounts::new(3000);
+        let queue = FaaQueue::new();
+        // Fills two rings and part of a third; the pops empty and retire the first.
+        for v in 0..3000 {
+            queue.push(counts.track(v));
+        }
+        for v in 0..1500 {
+            assert_eq!(queue.pop().map(|t| t.value), Some(v));
+        }
+        assert_eq!(counts.dropped(), 1500);
+        drop(queue);
+        counts.assert_all_dropped();
+    }
+
+    #[test]
+    fn ring_is_reused_while_it_has_room() {
+        let queue = FaaQueue::new();
+        let guard = pin();
+        let first = queue.head.load(Ordering::Relaxed, &guard);
+        // Many laps of the ring, never more than half full.
+        for v in 0..10 * RING {
+            queue.push(v);
+            if v >= RING / 2 {
+                assert_eq!(queue.pop(), Some(v - RING / 2));
+            }
+        }
+        assert_eq!(queue.head.load(Ordering::Relaxed, &guard), first);
+        assert_eq!(queue.tail.load(Ordering::Relaxed, &guard), first);
+        assert!(!queue.is_empty());
+    }
+}
@@ -0,0 +1,391 @@
+use std::{marker::PhantomData, ptr};
+
+#[cfg(not(loom))]
+use crate::reclaim::Reclaimer;
+use crate::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering, fence};
+
+/// Retired pointers a domain lets pile up before a `retire` scans them.
+#[cfg(not(loom))]
+const DEFAULT_THRESHOLD: usize = 128;
+#[cfg(loom)]
+const DEFAULT_THRESHOLD: usize = 1;
+
+/// One hazard pointer: the address its owner is reading, or null.
+struct Slot {
+    ptr: AtomicPtr<()>,
+    /// Owned by a [`HazardPointer`] (or, for the global domain, a thread's cache).
+    active: AtomicBool,
+    /// Slots are only ever prepended, and live as long as their domain.
+    next: *const Slot,
+}
+
+struct Retired {
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+    next: *mut Retired,
+}
+
+unsafe fn drop_box<T>(ptr: *mut ()) {
+    drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+}
+
+/// A hazard-pointer reclamation domain (Michael): readers publish the pointers they are about
+/// to dereference in hazard slots, and retired pointers are only freed once no slot holds
+/// them.
+///
+/// Unlike an epoch, which a single stalled pinned thread holds open for everyone, a stalled
+/// reader here keeps alive only the handful of nodes its own slots name, so the memory
+/// awaiting reclamation stays bounded by the threshold plus the number of slots in use. The
+/// cost moves to the read side: every protected load is a store, a fence and a re-load.
+pub struct Domain {
+    slots: AtomicPtr<Slot>,
+    retired: AtomicPtr<Retired>,
+    retired_count: AtomicUsize,
+    threshold: usize,
+}
+
+unsafe impl Send for Domain {}
+unsafe impl Sync for Domain {}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Domain {
+    pub fn new() -> Self {
+        Self::with_threshold(DEFAULT_THRESHOLD)
+    }
+
+    /// Creates a domain whose `retire` scans once `threshold` pointers are waiting. Lower
+    /// values bound memory more tightly at the cost of scanning the slots more often.
+    pub fn with_threshold(threshold: usize) -> Self {
+        Self {
+            slots: AtomicPtr::new(ptr::null_mut()),
+            retired: AtomicPtr::new(ptr::null_mut()),
+            retired_count: AtomicUsize::new(0),
+            threshold: threshold.max(1),
+        }
+    }
+
+    /// The process-wide domain, whose hazard slots are cached per thread.
+    #[cfg(not(loom))]
+    pub fn global() -> &'static Domain {
+        static GLOBAL: std::sync::OnceLock<Domain> = std::sync::OnceLock::new();
+        GLOBAL.get_or_init(Domain::new)
+    }
+
+    #[cfg(not(loom))]
+    fn is_global(&self) -> bool {
+        ptr::eq(self, Domain::global())
+    }
+
+    /// Claims an inactive slot, or links a new one.
+    fn acquire(&self) -> &Slot {
+        let mut curr = self.slots.load(Ordering::Acquire).cast_const();
+        while let Some(slot) = unsafe { curr.as_ref() } {
+            if !slot.active.load(Ordering::Relaxed)
+                && slot
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return slot;
+            }
+            curr = slot.next;
+        }
+
+        let slot = Box::into_raw(Box::new(Slot {
+            ptr: AtomicPtr::new(ptr::null_mut()),
+            active: AtomicBool::new(true),
+            next: ptr::null(),
+        }));
+        let mut head = self.slots.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*slot).next = head };
+            match self
+                .slots
+                .compare_exchange(head, slot, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return unsafe { &*slot },
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn release(slot: &Slot) {
+        slot.ptr.store(ptr::null_mut(), Ordering::Release);
+        slot.active.store(false, Ordering::Release);
+    }
+
+    /// Hands `ptr` to the domain, which drops it as a `Box<T>` once no hazard pointer
+    /// protects it, scanning right away if the threshold is reached.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `Box::into_raw`, must already be unreachable for readers that
+    /// have not protected it yet, and must not be retired twice. The box may be dropped on
+    /// whichever thread scans.
+    pub unsafe fn retire<T>(&self, ptr: *mut T) {
+        let node = Box::into_raw(Box::new(Retired {
+            ptr: ptr.cast(),
+            drop: drop_box::<T>,
+            next: ptr::null_mut(),
+        }));
+        self.push_retired(node, node);
+        if self.retired_count.fetch_add(1, Ordering::Relaxed) + 1 >= self.threshold {
+            self.scan();
+        }
+    }
+
+    /// Links the chain `first..=last` onto the retired list.
+    fn push_retired(&self, first: *mut Retired, last: *mut Retired) {
+        let mut head = self.retired.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*last).next = head };
+            match self
+                .retired
+                .compare_exchange(head, first, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Frees every retired pointer no slot protects, returning how many were freed.
+    pub fn scan(&self) -> usize {
+        let mut curr = self.retired.swap(ptr::null_mut(), Ordering::Acquire);
+        if curr.is_null() {
+            return 0;
+        }
+        // Pairs with the fence in `HazardPointer::try_protect`: either a reader's slot store
+        // is visible below, or its re-load saw the pointer already unlinked.
+        fence(Ordering::SeqCst);
+        let mut hazards = Vec::new();
+        let mut slot = self.slots.load(Ordering::Acquire).cast_const();
+        while let Some(s) = unsafe { slot.as_ref() } {
+            let p = s.ptr.load(Ordering::Acquire);
+            if !p.is_null() {
+                hazards.push(p);
+            }
+            slot = s.next;
+        }
+        hazards.sort_unstable();
+
+        let (mut kept_first, mut kept_last): (*mut Retired, *mut Retired) =
+            (ptr::null_mut(), ptr::null_mut());
+        let mut freed = 0;
+        while !curr.is_null() {
+            let node = curr;
+            curr = unsafe { (*node).next };
+            if hazards.binary_search(&unsafe { (*node).ptr }).is_ok() {
+                unsafe { (*node).next = kept_first };
+                if kept_last.is_null() {
+                    kept_last = node;
+                }
+                kept_first = node;
+            } else {
+                let node = unsafe { Box::from_raw(node) };
+                unsafe { (node.drop)(node.ptr) };
+                freed += 1;
+            }
+        }
+        if !kept_first.is_null() {
+            self.push_retired(kept_first, kept_last);
+        }
+        self.retired_count.fetch_sub(freed, Ordering::Relaxed);
+        freed
+    }
+}
+
+impl Drop for Domain {
+    fn drop(&mut self) {
+        // Every `HazardPointer` borrows the domain, so nothing is protected any more.
+        let mut curr = self.retired.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            unsafe { (node.drop)(node.ptr) };
+            curr = node.next;
+        }
+        let mut slot = self.slots.load(Ordering::Relaxed);
+        while !slot.is_null() {
+            let s = unsafe { Box::from_raw(slot) };
+            slot = s.next.cast_mut();
+        }
+    }
+}
+
+/// Slots of the global domain this thread has used, kept claimed for its next
+/// `HazardPointer` instead of going back to the shared list.
+#[cfg(not(loom))]
+struct LocalSlots(std::cell::RefCell<Vec<&'static Slot>>);
+
+#[cfg(not(loom))]
+impl Drop for LocalSlots {
+    fn drop(&mut self) {
+        self.0.get_mut().drain(..).for_each(Domain::release);
+    }
+}
+
+#[cfg(not(loom))]
+thread_local! {
+    static LOCAL: LocalSlots = const { LocalSlots(std::cell::RefCell::new(Vec::new())) };
+}
+
+/// Owns one hazard slot of a [`Domain`] for as long as it lives.
+pub struct HazardPointer<'d> {
+    #[cfg_attr(loom, allow(dead_code))]
+    domain: &'d Domain,
+    slot: &'d Slot,
+    /// The slot is tied to the owning thread's cache for the global domain.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'d> HazardPointer<'d> {
+    pub fn new(domain: &'d Domain) -> Self {
+        #[cfg(not(loom))]
+        if domain.is_global()
+            && let Some(slot) = LOCAL.try_with(|l| l.0.borrow_mut().pop()).ok().flatten()
+        {
+            return Self {
+                domain,
+                slot,
+                _not_send: PhantomData,
+            };
+        }
+        Self {
+            domain,
+            slot: domain.acquire(),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Loads `src` and protects what it points to, re-loading until the pointer published in
+    /// the slot is still the one in `src`. The result stays valid to dereference until this
+    /// hazard pointer protects something else, is reset or is dropped.
+    pub fn protect<T>(&mut self, src: &AtomicPtr<T>) -> *mut T {
+        let mut ptr = src.load(Ordering::Relaxed);
+        loop {
+            match self.try_protect(ptr, src) {
+                Ok(ptr) => return ptr,
+                Err(current) => ptr = current,
+            }
+        }
+    }
+
+    /// Publishes `ptr` and checks that `src` still holds it, returning what `src` holds
+    /// instead if not; only an `Ok` pointer is protected.
+    pub fn try_protect<T>(&mut self, ptr: *mut T, src: &AtomicPtr<T>) -> Result<*mut T, *mut T> {
+        self.protect_raw(ptr);
+        fence(Ordering::SeqCst);
+        let current = src.load(Ordering::Acquire);
+        if current == ptr {
+            Ok(ptr)
+        } else {
+            Err(current)
+        }
+    }
+
+    /// Publishes `ptr` without validating it; the caller must re-check that `ptr` is still
+    /// reachable after a `SeqCst` fence before dereferencing it.
+    pub fn protect_raw<T>(&mut self, ptr: *mut T) {
+        self.slot.ptr.store(ptr.cast(), Ordering::Release);
+    }
+
+    /// Stops protecting anything, without giving up the slot.
+    pub fn reset(&mut self) {
+        self.slot.ptr.store(ptr::null_mut(), Ordering::Release);
+    }
+}
+
+impl Drop for HazardPointer<'_> {
+    fn drop(&mut self) {
+        #[cfg(not(loom))]
+        if self.domain.is_global() {
+            self.reset();
+            // Lifetimes of global slots are 'static; the borrow only ties them to the domain.
+            let slot: &'static Slot = unsafe { &*(self.slot as *const Slot) };
+            if LOCAL.try_with(|l| l.0.borrow_mut().push(slot)).is_ok() {
+                return;
+            }
+        }
+        Domain::release(self.slot);
+    }
+}
+
+/// Hazard pointers of the [global](Domain::global) domain as a
+/// [`Reclaimer`](crate::reclaim::Reclaimer).
+///
+/// A guard backs each slot number it is asked for with one hazard pointer, taken from the
+/// thread's cache the first time and reused by every later `protect` in that slot. A guard
+/// thus holds as many hazard pointers as the highest slot its operations use, however long
+/// they run: three for a list traversal, and a stalled reader keeps at most that many nodes
+/// alive.
+#[cfg(not(loom))]
+pub struct Hazard;
+
+#[cfg(not(loom))]
+pub struct HazardGuard {
+    hazards: std::cell::RefCell<Vec<HazardPointer<'static>>>,
+}
+
+#[cfg(all(test, not(loom)))]
+impl HazardGuard {
+    /// Hazard pointers this guard holds.
+    pub(crate) fn slots(&self) -> usize {
+        self.hazards.borrow().len()
+    }
+}
+
+#[cfg(not(loom))]
+unsafe impl Reclaimer for Hazard {
+    type Guard = HazardGuard;
+
+    fn pin() -> HazardGuard {
+        HazardGuard {
+            hazards: std::cell::RefCell::new(Vec::new()),
+        }
+    }
+
+    fn repin(guard: &mut HazardGuard) {
+        guard
+            .hazards
+            .get_mut()
+            .iter_mut()
+            .for_each(HazardPointer::reset);
+    }
+
+    fn protect<T>(guard: &HazardGuard, slot: usize, src: &AtomicPtr<T>) -> *mut T {
+        let mut hazards = guard.hazards.borrow_mut();
+        if slot >= hazards.len() {
+            hazards.resize_with(slot + 1, || HazardPointer::new(Domain::global()));
+        }
+        let hp = &mut hazards[slot];
+        let mut ptr = src.load(Ordering::Acquire);
+        if ptr.is_null() {
+            hp.reset();
+            return ptr;
+        }
+        loop {
+            // Publish the address without the tag: that is what `retire` is handed.
+            hp.protect_raw(ptr.map_addr(|a| a & !(align_of::<T>() - 1)));
+            fence(Ordering::SeqCst);
+            let current = src.load(Ordering::Acquire);
+            if current == ptr {
+                break;
+            }
+            if current.is_null() {
+                hp.reset();
+                return current;
+            }
+            ptr = current;
+        }
+        ptr
+    }
+
+    unsafe fn retire<T>(_guard: &HazardGuard, ptr: *mut T) {
+        unsafe { Domain::global().retire(ptr) };
+    }
+}
@@ -1,12 +1,39 @@
+pub mod affinity;
+pub mod arrayqueue;
+pub mod asyncqueue;
+pub mod bench;
+pub mod broadcast;
+pub mod channel;
 pub mod clhlock;
 pub mod crossbeam_example;
+pub mod deque;
+pub mod elimination;
+pub mod exchanger;
+pub mod faaqueue;
+pub mod hazard;
 pub mod linearzibility;
 pub mod lockfreelist;
 pub mod locklink;
+#[cfg(all(test, loom))]
+mod loomtest;
 pub mod mcslock;
 pub mod mcsparklock;
 pub mod memord;
 pub mod msqueue;
 pub mod prosem;
+pub mod qsbr;
+pub mod reclaim;
+pub mod rawlock;
+pub mod rendezvous;
+pub mod select;
+pub mod spsc;
+pub mod taggedstack;
+#[cfg(all(test, not(loom)))]
+mod stresstest;
+mod sync;
+#[cfg(all(test, not(loom)))]
+mod testutil;
 pub mod ticketlock;
 pub mod treiberstack;
+pub mod twolockqueue;
+mod util;
@@ -1,38 +1,51 @@
-use std::{mem, sync::Arc, thread};
+use std::{marker::PhantomData, sync::Arc, thread};
 
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared};
+use crate::{
+    reclaim::{Epoch, Optimistic, Reclaimer},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+/// The mark of a logically deleted node, kept in the low bit of its `next`.
+fn tag<T>(ptr: *mut T) -> usize {
+    ptr.addr() & 1
+}
+
+fn with_tag<T>(ptr: *mut T, tag: usize) -> *mut T {
+    ptr.map_addr(|a| (a & !1) | tag)
+}
 
 pub struct Node<K, V> {
     key: K,
     value: V,
-    next: Atomic<Node<K, V>>,
+    next: AtomicPtr<Node<K, V>>,
 }
-pub struct List<K, V> {
-    head: Atomic<Node<K, V>>,
+pub struct List<K, V, R: Reclaimer = Epoch> {
+    head: AtomicPtr<Node<K, V>>,
+    _reclaimer: PhantomData<R>,
 }
-unsafe impl<K, V> Send for List<K, V>
+unsafe impl<K, V, R: Reclaimer> Send for List<K, V, R>
 where
     K: Send,
     V: Send,
 {
 }
-unsafe impl<K, V> Sync for List<K, V>
+unsafe impl<K, V, R: Reclaimer> Sync for List<K, V, R>
 where
     K: Sync,
     V: Sync,
 {
 }
 
-impl<K, V> Default for List<K, V> {
+impl<K, V, R: Reclaimer> Default for List<K, V, R> {
     fn default() -> Self {
-        Self::new()
+        Self::with_reclaimer()
     }
 }
 
 impl<K, V> Node<K, V> {
     pub fn new(key: K, value: V) -> Self {
         Self {
-            next: Atomic::null(),
+            next: AtomicPtr::new(std::ptr::null_mut()),
             key,
             value,
         }
@@ -42,49 +55,69 @@ impl<K, V> Node<K, V> {
     }
 }
 
-impl<K, V> Drop for List<K, V> {
+impl<K, V, R: Reclaimer> Drop for List<K, V, R> {
     fn drop(&mut self) {
-        let mut o_curr = mem::take(&mut self.head);
-        while let Some(curr) = unsafe { o_curr.try_into_owned() }.map(|s| s.into_box()) {
-            o_curr = curr.next
+        let mut o_curr = self.head.load(Ordering::Relaxed);
+        while !o_curr.is_null() {
+            let curr = unsafe { Box::from_raw(with_tag(o_curr, 0)) };
+            o_curr = curr.next.load(Ordering::Relaxed);
         }
     }
 }
-struct Cursor<'g, K, V> {
-    prev: &'g Atomic<Node<K, V>>,
-    curr: Shared<'g, Node<K, V>>,
+struct Cursor<'g, K, V, R: Reclaimer> {
+    prev: &'g AtomicPtr<Node<K, V>>,
+    curr: *mut Node<K, V>,
+    /// Guard slots protecting the node `prev` points into and `curr`; `find_hm` protects
+    /// `next` in the third one and rotates.
+    prev_slot: usize,
+    curr_slot: usize,
+    _reclaimer: PhantomData<R>,
 }
 
-impl<K, V> Clone for Cursor<'_, K, V> {
+impl<K, V, R: Reclaimer> Clone for Cursor<'_, K, V, R> {
     fn clone(&self) -> Self {
         Self {
             prev: self.prev,
             curr: self.curr,
+            prev_slot: self.prev_slot,
+            curr_slot: self.curr_slot,
+            _reclaimer: PhantomData,
         }
     }
 }
 
-impl<'g, K, V> Cursor<'g, K, V>
+impl<'g, K, V, R: Reclaimer> Cursor<'g, K, V, R>
 where
     K: Ord,
 {
-    pub fn new(prev: &'g Atomic<Node<K, V>>, curr: Shared<'g, Node<K, V>>) -> Self {
+    /// `curr` must be protected in slot 0.
+    pub fn new(prev: &'g AtomicPtr<Node<K, V>>, curr: *mut Node<K, V>) -> Self {
         Self {
             prev,
-            curr: curr.with_tag(0),
+            curr: with_tag(curr, 0),
+            prev_slot: 1,
+            curr_slot: 0,
+            _reclaimer: PhantomData,
         }
     }
 
+    /// Of the three slots a traversal uses, the one protecting neither `prev` nor `curr`.
+    fn spare_slot(&self) -> usize {
+        3 - self.prev_slot - self.curr_slot
+    }
+
+    /// Only sound under an [`Optimistic`] reclaimer: it walks the `next` links of marked
+    /// nodes, which may already be unlinked.
     #[inline]
-    pub fn find_h(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    pub fn find_h(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
         let mut prev_next = self.curr;
         let found = loop {
             let Some(curr) = (unsafe { self.curr.as_ref() }) else {
                 return Ok(false);
             };
 
-            let next = curr.next.load(std::sync::atomic::Ordering::Acquire, guard);
-            while next.tag() != 0 {
+            let next = R::protect(guard, 0, &curr.next);
+            while tag(next) != 0 {
                 self.curr = next;
                 continue;
             }
@@ -102,52 +135,49 @@ where
             return Ok(found);
         } else {
             self.prev
-                .compare_exchange(
-                    prev_next,
-                    self.curr,
-                    std::sync::atomic::Ordering::Release,
-                    std::sync::atomic::Ordering::Relaxed,
-                    guard,
-                )
+                .compare_exchange(prev_next, self.curr, Ordering::Release, Ordering::Relaxed)
                 .map_err(|_| ())?;
             let mut node = prev_next;
-            while node.with_tag(0) != self.curr {
-                let next = unsafe { node.deref() }
-                    .next
-                    .load(std::sync::atomic::Ordering::Relaxed, guard);
-                unsafe { guard.defer_destroy(node) };
+            while with_tag(node, 0) != self.curr {
+                let next = unsafe { &*with_tag(node, 0) }.next.load(Ordering::Relaxed);
+                unsafe { R::retire(guard, with_tag(node, 0)) };
                 node = next;
             }
             Ok(found)
         }
     }
 
-    pub fn find_hm(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    /// Protects every node before stepping onto it, and only steps through a `next` it found
+    /// unmarked or just unlinked, so it also works under hazard pointers. Hand over hand: only
+    /// `prev`'s node, `curr` and `next` are protected at a time, `next` in the slot of the
+    /// node just left behind.
+    pub fn find_hm(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
         loop {
-            debug_assert_eq!(self.curr.tag(), 0);
+            debug_assert_eq!(tag(self.curr), 0);
             let Some(curr) = (unsafe { self.curr.as_ref() }) else {
                 break Ok(false);
             };
-            let mut next = curr.next.load(std::sync::atomic::Ordering::Acquire, guard);
-            if next.tag() != 0 {
-                next = next.with_tag(0);
+            let spare = self.spare_slot();
+            let mut next = R::protect(guard, spare, &curr.next);
+            if tag(next) != 0 {
+                next = with_tag(next, 0);
                 self.prev
-                    .compare_exchange(
-                        self.curr,
-                        next,
-                        std::sync::atomic::Ordering::Release,
-                        std::sync::atomic::Ordering::Relaxed,
-                        guard,
-                    )
+                    .compare_exchange(self.curr, next, Ordering::Release, Ordering::Relaxed)
                     .map_err(|_| ())?;
-                unsafe { guard.defer_destroy(self.curr) };
+                unsafe { R::retire(guard, self.curr) };
+                // `prev` stays put; `curr`'s slot is the spare one now.
                 self.curr = next;
+                self.curr_slot = spare;
                 continue;
             }
             match curr.key.cmp(key) {
                 std::cmp::Ordering::Less => {
                     self.prev = &curr.next;
-                    self.curr = curr.next.load(std::sync::atomic::Ordering::Acquire, guard)
+                    // Reuse the unmarked `next` checked above: reloading could observe a mark set
+                    // since, and an insert behind a deleted node would be lost with it.
+                    self.curr = next;
+                    self.prev_slot = self.curr_slot;
+                    self.curr_slot = spare;
                 }
                 std::cmp::Ordering::Equal => return Ok(true),
                 std::cmp::Ordering::Greater => return Ok(false),
@@ -155,8 +185,9 @@ where
         }
     }
 
+    /// Only sound under an [`Optimistic`] reclaimer, like [`Cursor::find_h`].
     #[inline]
-    pub fn find_hms(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    pub fn find_hms(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
         Ok(loop {
             let a = unsafe { self.curr.as_ref() };
             let a = match a {
@@ -166,14 +197,10 @@ where
             match a.key.cmp(key) {
                 std::cmp::Ordering::Less => {
                     self.prev = &a.next;
-                    self.curr = a.next.load(std::sync::atomic::Ordering::Acquire, guard)
+                    self.curr = with_tag(R::protect(guard, 0, &a.next), 0)
                 }
                 std::cmp::Ordering::Equal => {
-                    break a
-                        .next
-                        .load(std::sync::atomic::Ordering::Release, guard)
-                        .tag()
-                        == 0;
+                    break tag(a.next.load(Ordering::Acquire)) == 0;
                 }
                 std::cmp::Ordering::Greater => break false,
             }
@@ -187,61 +214,62 @@ where
     #[inline]
     pub fn insert(
         &mut self,
-        mut node: Owned<Node<K, V>>,
-        guard: &'g Guard,
-    ) -> Result<(), Owned<Node<K, V>>> {
-        node.next = self.curr.into();
-        match self.prev.compare_exchange(
-            self.curr,
-            node,
-            std::sync::atomic::Ordering::Release,
-            std::sync::atomic::Ordering::Relaxed,
-            guard,
-        ) {
-            Ok(node) => {
+        node: Box<Node<K, V>>,
+        _guard: &'g R::Guard,
+    ) -> Result<(), Box<Node<K, V>>> {
+        node.next.store(self.curr, Ordering::Relaxed);
+        let node = Box::into_raw(node);
+        match self
+            .prev
+            .compare_exchange(self.curr, node, Ordering::Release, Ordering::Relaxed)
+        {
+            Ok(_) => {
                 self.curr = node;
                 Ok(())
             }
-            Err(e) => Err(e.new),
+            Err(_) => Err(unsafe { Box::from_raw(node) }),
         }
     }
     #[inline]
-    pub fn delete(&mut self, guard: &'g Guard) -> Result<&'g V, ()> {
+    pub fn delete(&mut self, guard: &'g R::Guard) -> Result<&'g V, ()> {
         let curr_node = unsafe { self.curr.as_ref() }.unwrap();
-        let next = curr_node
-            .next
-            .fetch_or(1, std::sync::atomic::Ordering::AcqRel, guard);
-        if next.tag() == 1 {
-            return Err(());
-        } else {
-            if self
-                .prev
-                .compare_exchange(
-                    self.curr,
-                    next,
-                    std::sync::atomic::Ordering::Release,
-                    std::sync::atomic::Ordering::Relaxed,
-                    guard,
-                )
-                .is_ok()
-            {
-                unsafe {
-                    guard.defer_destroy(self.curr);
-                }
+        // Set the mark with a CAS loop rather than `fetch_or`, which loom's `AtomicPtr` lacks.
+        let mut next = curr_node.next.load(Ordering::Acquire);
+        loop {
+            if tag(next) == 1 {
+                return Err(());
+            }
+            match curr_node.next.compare_exchange(
+                next,
+                with_tag(next, 1),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => break,
+                Err(current) => next = current,
+            }
+        }
+        if self
+            .prev
+            .compare_exchange(self.curr, next, Ordering::Release, Ordering::Relaxed)
+            .is_ok()
+        {
+            unsafe {
+                R::retire(guard, self.curr);
             }
-            self.curr = next;
-            Ok(&curr_node.value)
         }
+        self.curr = next;
+        Ok(&curr_node.value)
     }
-    pub fn h(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    pub fn h(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
         let mut prev_next = self.curr;
         let found = loop {
             let Some(curr) = (unsafe { self.curr.as_ref() }) else {
                 break false;
             };
-            let next = curr.next.load(std::sync::atomic::Ordering::Acquire, guard);
-            if next.tag() != 0 {
-                self.curr = next;
+            let next = R::protect(guard, 0, &curr.next);
+            if tag(next) != 0 {
+                self.curr = with_tag(next, 0);
                 continue;
             }
             match curr.key.cmp(key) {
@@ -258,54 +286,44 @@ where
             return Ok(found);
         } else {
             self.prev
-                .compare_exchange(
-                    prev_next,
-                    self.curr,
-                    std::sync::atomic::Ordering::Release,
-                    std::sync::atomic::Ordering::Relaxed,
-                    guard,
-                )
+                .compare_exchange(prev_next, self.curr, Ordering::Release, Ordering::Relaxed)
                 .map_err(|_| ())?;
             let mut node = prev_next;
-            while node.with_tag(0) != self.curr {
-                let next = unsafe { node.deref() }
-                    .next
-                    .load(std::sync::atomic::Ordering::Relaxed, guard);
-                unsafe { guard.defer_destroy(node) };
+            while with_tag(node, 0) != self.curr {
+                let next = unsafe { &*with_tag(node, 0) }.next.load(Ordering::Relaxed);
+                unsafe { R::retire(guard, with_tag(node, 0)) };
                 node = next;
             }
             Ok(found)
         }
     }
 
-    pub fn hm(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    pub fn hm(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
         loop {
-            debug_assert_eq!(self.curr.tag(), 0);
+            debug_assert_eq!(tag(self.curr), 0);
             let Some(curr) = (unsafe { self.curr.as_ref() }) else {
                 break Ok(false);
             };
-            let mut next = curr.next.load(std::sync::atomic::Ordering::Acquire, guard);
-            if next.tag() != 0 {
-                next = next.with_tag(0);
+            let spare = self.spare_slot();
+            let mut next = R::protect(guard, spare, &curr.next);
+            if tag(next) != 0 {
+                next = with_tag(next, 0);
                 self.prev
-                    .compare_exchange(
-                        self.curr,
-                        next,
-                        std::sync::atomic::Ordering::Release,
-                        std::sync::atomic::Ordering::Relaxed,
-                        guard,
-                    )
+                    .compare_exchange(self.curr, next, Ordering::Release, Ordering::Relaxed)
                     .map_err(|_| ())?;
                 unsafe {
-                    guard.defer_destroy(self.curr);
+                    R::retire(guard, self.curr);
                 };
                 self.curr = next;
+                self.curr_slot = spare;
                 continue;
             }
             match curr.key.cmp(key) {
                 std::cmp::Ordering::Less => {
                     self.prev = &curr.next;
                     self.curr = next;
+                    self.prev_slot = self.curr_slot;
+                    self.curr_slot = spare;
                 }
                 std::cmp::Ordering::Equal => break Ok(true),
                 std::cmp::Ordering::Greater => break Ok(false),
@@ -313,23 +331,19 @@ where
         }
     }
 
-    pub fn hms(&mut self, key: &K, guard: &'g Guard) -> Result<bool, ()> {
+    pub fn hms(&mut self, key: &K, guard: &'g R::Guard) -> Result<bool, ()> {
         Ok(loop {
             let Some(curr) = (unsafe { self.curr.as_ref() }) else {
                 break false;
             };
-            let next = curr.next.load(std::sync::atomic::Ordering::Acquire, guard);
+            let next = R::protect(guard, 0, &curr.next);
             match curr.key.cmp(key) {
                 std::cmp::Ordering::Less => {
                     self.prev = &curr.next;
-                    self.curr = next;
+                    self.curr = with_tag(next, 0);
                 }
                 std::cmp::Ordering::Equal => {
-                    break curr
-                        .next
-                        .load(std::sync::atomic::Ordering::Acquire, guard)
-                        .tag()
-                        == 0;
+                    break tag(curr.next.load(Ordering::Acquire)) == 0;
                 }
                 std::cmp::Ordering::Greater => break false,
             }
@@ -339,26 +353,30 @@ where
 
 impl<K, V> List<K, V> {
     pub fn new() -> Self {
+        Self::with_reclaimer()
+    }
+}
+impl<K, V, R: Reclaimer> List<K, V, R> {
+    /// Creates a list that frees deleted nodes through `R` instead of the default epoch.
+    pub fn with_reclaimer() -> Self {
         List {
-            head: Atomic::null(),
+            head: AtomicPtr::new(std::ptr::null_mut()),
+            _reclaimer: PhantomData,
         }
     }
 }
-impl<K, V> List<K, V>
+impl<K, V, R: Reclaimer> List<K, V, R>
 where
     K: Ord,
 {
     #[inline]
-    pub fn head<'g>(&'g self, guard: &'g Guard) -> Cursor<'g, K, V> {
-        Cursor::new(
-            &self.head,
-            self.head.load(std::sync::atomic::Ordering::Acquire, guard),
-        )
+    pub fn head<'g>(&'g self, guard: &'g R::Guard) -> Cursor<'g, K, V, R> {
+        Cursor::new(&self.head, R::protect(guard, 0, &self.head))
     }
     #[inline]
-    fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g Guard) -> (bool, Cursor<'g, K, V>)
+    fn find<'g, F>(&'g self, key: &K, find: &F, guard: &'g R::Guard) -> (bool, Cursor<'g, K, V, R>)
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
         loop {
             let mut cursor = self.head(guard);
@@ -368,19 +386,19 @@ where
         }
     }
     #[inline]
-    fn lookup<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
+    fn lookup<'g, F>(&'g self, key: &K, find: F, guard: &'g R::Guard) -> Option<&'g V>
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
         let (found, cursor) = self.find(key, &find, guard);
         if found { Some(cursor.lookup()) } else { None }
     }
     #[inline]
-    fn insert<'g, F>(&'g self, key: K, value: V, find: F, guard: &'g Guard) -> bool
+    fn insert<'g, F>(&'g self, key: K, value: V, find: F, guard: &'g R::Guard) -> bool
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
-        let mut node = Owned::new(Node::new(key, value));
+        let mut node = Box::new(Node::new(key, value));
         loop {
             let (found, mut cursor) = self.find(&node.key, &find, guard);
             if found {
@@ -394,9 +412,9 @@ where
     }
 
     #[inline]
-    fn delete<'g, F>(&'g self, key: &K, find: F, guard: &'g Guard) -> Option<&'g V>
+    fn delete<'g, F>(&'g self, key: &K, find: F, guard: &'g R::Guard) -> Option<&'g V>
     where
-        F: Fn(&mut Cursor<'g, K, V>, &K, &'g Guard) -> Result<bool, ()>,
+        F: Fn(&mut Cursor<'g, K, V, R>, &K, &'g R::Guard) -> Result<bool, ()>,
     {
         loop {
             let (found, mut cursor) = self.find(key, &find, guard);
@@ -408,37 +426,52 @@ where
             }
         }
     }
-    pub fn harris_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.lookup(key, Cursor::find_h, guard)
-    }
-
-    /// Insert the value with the Harris strategy.
-    pub fn harris_insert<'g>(&'g self, key: K, value: V, guard: &'g Guard) -> bool {
-        self.insert(key, value, Cursor::find_h, guard)
-    }
-
-    /// Attempts to delete the value with the Harris strategy.
-    pub fn harris_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
-        self.delete(key, Cursor::find_h, guard)
-    }
 
     /// Lookups the value at `key` with the Harris-Michael strategy.
-    pub fn harris_michael_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+    ///
+    /// Takes the guard mutably, like [`Queue::peek`](crate::msqueue::Queue::peek): the value
+    /// is protected by one of the guard's slots, which the next operation would reuse.
+    pub fn harris_michael_lookup<'g>(&'g self, key: &K, guard: &'g mut R::Guard) -> Option<&'g V> {
         self.lookup(key, Cursor::find_hm, guard)
     }
 
     /// Insert a `key`-`value`` pair with the Harris-Michael strategy.
-    pub fn harris_michael_insert(&self, key: K, value: V, guard: &Guard) -> bool {
+    pub fn harris_michael_insert(&self, key: K, value: V, guard: &R::Guard) -> bool {
         self.insert(key, value, Cursor::find_hm, guard)
     }
 
-    /// Delete the value at `key` with the Harris-Michael strategy.
-    pub fn harris_michael_delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+    /// Delete the value at `key` with the Harris-Michael strategy. The guard is taken mutably
+    /// for the same reason as in [`List::harris_michael_lookup`].
+    pub fn harris_michael_delete<'g>(&'g self, key: &K, guard: &'g mut R::Guard) -> Option<&'g V> {
         self.delete(key, Cursor::find_hm, guard)
     }
+}
+
+/// The strategies that read through marked nodes, which hazard pointers cannot protect.
+impl<K, V, R: Optimistic> List<K, V, R>
+where
+    K: Ord,
+{
+    pub fn harris_lookup<'g>(&'g self, key: &K, guard: &'g R::Guard) -> Option<&'g V> {
+        self.lookup(key, Cursor::find_h, guard)
+    }
+
+    /// Insert the value with the Harris strategy.
+    pub fn harris_insert<'g>(&'g self, key: K, value: V, guard: &'g R::Guard) -> bool {
+        self.insert(key, value, Cursor::find_h, guard)
+    }
+
+    /// Attempts to delete the value with the Harris strategy.
+    pub fn harris_delete<'g>(&'g self, key: &K, guard: &'g R::Guard) -> Option<&'g V> {
+        self.delete(key, Cursor::find_h, guard)
+    }
 
     /// Lookups the value at `key` with the Harris-Herlihy-Shavit strategy.
-    pub fn harris_herlihy_shavit_lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+    pub fn harris_herlihy_shavit_lookup<'g>(
+        &'g self,
+        key: &K,
+        guard: &'g R::Guard,
+    ) -> Option<&'g V> {
         self.lookup(key, Cursor::find_hms, guard)
     }
 }
@@ -472,7 +505,7 @@ pub fn lc() {
     println!("\n--- Lookup Phase ---");
     // Look up some keys
     {
-        let guard = &epoch::pin();
+        let guard = &mut epoch::pin();
         for key in [3, 12, 21, 30, 33] {
             match list.harris_michael_lookup(&key, guard) {
                 Some(v) => println!("Lookup key {key}: Found value '{}'", v),
@@ -484,7 +517,7 @@ pub fn lc() {
     println!("\n--- Deletion Phase ---");
     // Delete a few keys
     {
-        let guard = &epoch::pin();
+        let guard = &mut epoch::pin();
         for key in [12, 21] {
             match list.harris_michael_delete(&key, guard) {
                 Some(v) => println!("Deleted key {key}: value '{}'", v),
@@ -496,7 +529,7 @@ pub fn lc() {
     println!("\n--- Post-Deletion Lookup ---");
     // Re-lookup deleted keys
     {
-        let guard = &epoch::pin();
+        let guard = &mut epoch::pin();
         for key in [12, 21] {
             match list.harris_michael_lookup(&key, guard) {
                 Some(v) => println!("Lookup key {key}: Found value '{}'", v),
@@ -507,3 +540,52 @@ pub fn lc() {
 
     println!("\nDone.");
 }
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::{
+        hazard::{Domain, Hazard},
+        testutil::{DropCounts, scan_global_until},
+    };
+
+    #[test]
+    fn deleted_value_outlives_its_protection() {
+        let counts = DropCounts::new(100);
+        let list = List::<_, _, Hazard>::with_reclaimer();
+        let mut guard = Hazard::pin();
+        // Values are tracked in key order, so key `k` has tracker id `k`.
+        for k in 0..100 {
+            assert!(list.harris_michael_insert(k, counts.track(k), &guard));
+        }
+        for k in 0..100 {
+            let v = list.harris_michael_delete(&k, &mut guard).unwrap();
+            Domain::global().scan();
+            assert!(!counts.is_dropped(k), "freed while still protected");
+            assert_eq!(v.value, k);
+        }
+        drop(guard);
+        scan_global_until(|| counts.dropped() == 100);
+        counts.assert_all_dropped();
+    }
+
+    #[test]
+    fn traversals_reuse_a_fixed_set_of_slots() {
+        let list = List::<_, _, Hazard>::with_reclaimer();
+        let mut guard = Hazard::pin();
+        for k in 0..100 {
+            assert!(list.harris_michael_insert(k, k, &guard));
+        }
+        let slots = guard.slots();
+        assert!(slots <= 3, "a traversal held {slots} slots");
+        // Long walks, unlinking and relinking, all on the one guard.
+        for _ in 0..10 {
+            for k in (0..100).rev() {
+                assert_eq!(list.harris_michael_lookup(&k, &mut guard), Some(&k));
+                assert_eq!(list.harris_michael_delete(&k, &mut guard), Some(&k));
+                assert!(list.harris_michael_insert(k, k, &guard));
+            }
+            assert_eq!(guard.slots(), slots);
+        }
+    }
+}
@@ -0,0 +1,656 @@
+//! Model-checked tests: every interleaving (up to the preemption bound) of small two-thread
+//! workloads against the locks and the epoch-based structures. Only built with `--cfg loom`.
+
+use std::{ptr, time::Duration};
+
+use crossbeam_epoch::pin;
+use loom::{
+    cell::UnsafeCell,
+    model::Builder,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicPtr, Ordering},
+    },
+    thread,
+};
+
+use crate::{
+    arrayqueue::ArrayQueue,
+    asyncqueue::AsyncQueue,
+    broadcast::{self, Mode, RecvError},
+    channel,
+    clhlock::Clhlock,
+    deque::{Steal, deque},
+    elimination::EliminationStack,
+    exchanger::Exchanger,
+    faaqueue::FaaQueue,
+    hazard::{Domain as HazardDomain, HazardPointer},
+    lockfreelist::List,
+    mcslock::McsLock,
+    mcsparklock::McsParkLock,
+    msqueue::Queue,
+    qsbr::Domain as QsbrDomain,
+    rawlock::RawLock,
+    rendezvous::SynchronousQueue,
+    select::Select,
+    spsc,
+    taggedstack::TaggedStack,
+    ticketlock::TicketLock,
+    treiberstack::Stack,
+    twolockqueue::TwoLockQueue,
+};
+
+/// Runs `f` under loom, bounding preemptions unless `LOOM_MAX_PREEMPTIONS` asks otherwise:
+/// the epoch machinery behind every pin makes the unbounded state space impractically large.
+fn model<F>(f: F)
+where
+    F: Fn() + Sync + Send + 'static,
+{
+    let mut builder = Builder::new();
+    if builder.preemption_bound.is_none() {
+        builder.preemption_bound = Some(3);
+    }
+    builder.check(f);
+}
+
+struct Guarded<L> {
+    lock: L,
+    value: UnsafeCell<usize>,
+}
+
+unsafe impl<L: Sync> Sync for Guarded<L> {}
+
+/// Two threads increment a loom `UnsafeCell` under the lock; loom fails the test if the two
+/// accesses are ever concurrent, so this checks exclusion rather than just the final count.
+fn mutual_exclusion<L: RawLock + 'static>() {
+    model(|| {
+        let shared = Arc::new(Guarded {
+            lock: L::default(),
+            value: UnsafeCell::new(0),
+        });
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let token = shared.lock.lock();
+                    shared.value.with_mut(|v| unsafe { *v += 1 });
+                    shared.lock.unlock(token);
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        let token = shared.lock.lock();
+        assert_eq!(shared.value.with(|v| unsafe { *v }), 2);
+        shared.lock.unlock(token);
+    });
+}
+
+#[test]
+fn ticketlock_mutual_exclusion() {
+    mutual_exclusion::<TicketLock>();
+}
+
+#[test]
+fn clhlock_mutual_exclusion() {
+    mutual_exclusion::<Clhlock>();
+}
+
+#[test]
+fn mcslock_mutual_exclusion() {
+    mutual_exclusion::<McsLock>();
+}
+
+#[test]
+fn mcsparklock_mutual_exclusion() {
+    mutual_exclusion::<McsParkLock>();
+}
+
+#[test]
+fn stack_push_pop() {
+    model(|| {
+        let stack = Arc::new(Stack::new());
+        let other = Arc::clone(&stack);
+        let handle = thread::spawn(move || {
+            other.push(1);
+            other.pop()
+        });
+        stack.push(2);
+        let mine = stack.pop();
+        let theirs = handle.join().unwrap();
+
+        let mut popped = [mine.unwrap(), theirs.unwrap()];
+        popped.sort_unstable();
+        assert_eq!(popped, [1, 2]);
+        assert!(stack.is_empty());
+    });
+}
+
+#[test]
+fn elimination_push_pop() {
+    model(|| {
+        let stack = Arc::new(EliminationStack::new());
+        stack.push(1);
+        let other = Arc::clone(&stack);
+        let handle = thread::spawn(move || other.pop());
+        stack.push(2);
+        let theirs = handle.join().unwrap();
+
+        // Whether the pop went through `head` or met the push in the slot, nothing is lost.
+        let mut popped: Vec<_> = theirs.into_iter().collect();
+        popped.extend(stack.pop());
+        popped.extend(stack.pop());
+        popped.sort_unstable();
+        assert_eq!(popped, [1, 2]);
+        assert!(stack.is_empty());
+    });
+}
+
+#[test]
+fn exchanger_swap() {
+    model(|| {
+        let exchanger = Arc::new(Exchanger::new());
+        let other = Arc::clone(&exchanger);
+        // Long enough that neither side gives up: loom's timed park only yields.
+        let timeout = Duration::from_secs(3600);
+        let handle = thread::spawn(move || other.exchange(1, timeout));
+        assert_eq!(exchanger.exchange(2, timeout), Ok(1));
+        assert_eq!(handle.join().unwrap(), Ok(2));
+    });
+}
+
+#[test]
+fn stack_push_all_take_all() {
+    model(|| {
+        let stack = Arc::new(Stack::new());
+        stack.push(0);
+        let other = Arc::clone(&stack);
+        let handle = thread::spawn(move || other.push_all([1, 2]));
+        let taken: Vec<_> = stack.take_all().collect();
+        handle.join().unwrap();
+
+        // The batch lands whole on one side of the swap, on top of what was there.
+        let rest: Vec<_> = stack.take_all().collect();
+        match (taken.as_slice(), rest.as_slice()) {
+            ([0], [2, 1]) | ([2, 1, 0], []) => {}
+            other => panic!("batch split or reordered: {other:?}"),
+        }
+    });
+}
+
+#[test]
+fn tagged_stack_push_pop() {
+    model(|| {
+        let stack = Arc::new(TaggedStack::new());
+        stack.push(1);
+        let other = Arc::clone(&stack);
+        // Pop and push back, so the other thread's pop can see the same node return to top.
+        let handle = thread::spawn(move || {
+            let v = other.pop();
+            other.push(3);
+            v
+        });
+        stack.push(2);
+        let mine = stack.pop();
+        let theirs = handle.join().unwrap();
+
+        let mut popped: Vec<_> = mine.into_iter().chain(theirs).collect();
+        popped.extend(stack.pop());
+        popped.extend(stack.pop());
+        popped.sort_unstable();
+        assert_eq!(popped, [1, 2, 3]);
+        assert!(stack.is_empty());
+    });
+}
+
+/// Records its own drop in a flag that outlives it, so a reader can tell a freed node apart
+/// without touching its memory.
+struct DropFlag(Arc<AtomicBool>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Two nodes, the first published in the returned pointer, with their drop flags.
+fn flagged_pair() -> (
+    *mut DropFlag,
+    *mut DropFlag,
+    [Arc<AtomicBool>; 2],
+    Arc<AtomicPtr<DropFlag>>,
+) {
+    let flags = [(); 2].map(|_| Arc::new(AtomicBool::new(false)));
+    let first = Box::into_raw(Box::new(DropFlag(Arc::clone(&flags[0]))));
+    let second = Box::into_raw(Box::new(DropFlag(Arc::clone(&flags[1]))));
+    (first, second, flags, Arc::new(AtomicPtr::new(first)))
+}
+
+/// Checks, around a yield, that the node `p` names among `first` and the other one is not
+/// freed.
+fn assert_not_freed(p: *mut DropFlag, first: usize, flags: &[Arc<AtomicBool>; 2]) {
+    let freed = &flags[usize::from(p as usize != first)];
+    assert!(!freed.load(Ordering::Relaxed), "protected node was freed");
+    thread::yield_now();
+    assert!(!freed.load(Ordering::Relaxed), "protected node was freed");
+}
+
+#[test]
+fn hazard_protect_retire() {
+    model(|| {
+        let domain = Arc::new(HazardDomain::with_threshold(1));
+        let (first, second, flags, src) = flagged_pair();
+
+        let (d, s) = (Arc::clone(&domain), Arc::clone(&src));
+        let first_addr = first as usize;
+        let reader = thread::spawn(move || {
+            let mut hp = HazardPointer::new(&d);
+            // A threshold of one scans on every retire, so a missed hazard frees the node.
+            assert_not_freed(hp.protect(&s), first_addr, &flags);
+        });
+        src.store(second, Ordering::Release);
+        unsafe { domain.retire(first) };
+        reader.join().unwrap();
+
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        unsafe { domain.retire(second) };
+        domain.scan();
+    });
+}
+
+#[test]
+fn qsbr_register_retire() {
+    model(|| {
+        let domain = Arc::new(QsbrDomain::new());
+        let (first, second, flags, src) = flagged_pair();
+
+        let (d, s) = (Arc::clone(&domain), Arc::clone(&src));
+        let first_addr = first as usize;
+        let reader = thread::spawn(move || {
+            // Registering races with the retire: either the writer counts us, or we already
+            // read the replacement.
+            let registration = d.register();
+            assert_not_freed(s.load(Ordering::Acquire), first_addr, &flags);
+            registration.quiescent();
+        });
+        let registration = domain.register();
+        src.store(second, Ordering::Release);
+        // A threshold of one tries to free on every retire.
+        unsafe { registration.retire(first) };
+        registration.quiescent();
+        reader.join().unwrap();
+
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        unsafe { registration.retire(second) };
+    });
+}
+
+#[test]
+fn queue_push_pop() {
+    model(|| {
+        let queue = Arc::new(Queue::new());
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut guard = pin();
+                let mut popped = Vec::new();
+                for _ in 0..2 {
+                    popped.extend(queue.pop_with_guard(&mut guard));
+                }
+                popped
+            })
+        };
+        let mut guard = pin();
+        queue.push_with_guard(1, &mut guard);
+        queue.push_with_guard(2, &mut guard);
+
+        // Whatever the consumer got plus what is left must be the pushes, in order.
+        let mut popped = consumer.join().unwrap();
+        while let Some(v) = queue.pop_with_guard(&mut guard) {
+            popped.push(v);
+        }
+        assert_eq!(popped, [1, 2]);
+    });
+}
+
+#[test]
+fn arrayqueue_full_push_pop() {
+    model(|| {
+        let queue = Arc::new(ArrayQueue::new(1));
+        let other = Arc::clone(&queue);
+        let handle = thread::spawn(move || other.push(1).is_ok());
+        let mine = queue.push(2).is_ok();
+        let theirs = handle.join().unwrap();
+
+        // One slot: exactly one push fits until the pop frees it again.
+        assert!(mine ^ theirs);
+        assert_eq!(queue.pop(), Some(if mine { 2 } else { 1 }));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.push(3).is_ok());
+    });
+}
+
+#[test]
+fn arrayqueue_push_pop() {
+    model(|| {
+        let queue = Arc::new(ArrayQueue::new(2));
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut popped = Vec::new();
+                for _ in 0..2 {
+                    popped.extend(queue.pop());
+                }
+                popped
+            })
+        };
+        queue.push(1).unwrap();
+        queue.push(2).unwrap();
+
+        let mut popped = consumer.join().unwrap();
+        popped.extend(queue.pop());
+        popped.extend(queue.pop());
+        assert_eq!(popped, [1, 2]);
+    });
+}
+
+#[test]
+fn spsc_ring_batches() {
+    model(|| {
+        let (mut producer, mut consumer) = spsc::ring(2);
+        let handle = thread::spawn(move || {
+            let mut sent = producer.push_slice(&[1, 2, 3]);
+            while sent < 3 {
+                sent += producer.push(sent + 1).map_or(0, |()| 1);
+                thread::yield_now();
+            }
+        });
+        let mut popped = Vec::new();
+        let mut buf = [0; 2];
+        while popped.len() < 3 {
+            let n = consumer.pop_into(&mut buf);
+            popped.extend_from_slice(&buf[..n]);
+            thread::yield_now();
+        }
+        handle.join().unwrap();
+        assert_eq!(popped, [1, 2, 3]);
+        assert_eq!(consumer.pop(), None);
+    });
+}
+
+#[test]
+fn deque_pop_steal_last() {
+    model(|| {
+        let (worker, stealer) = deque();
+        worker.push(1);
+        let thief = thread::spawn(move || stealer.steal());
+        let mine = worker.pop();
+        let theirs = thief.join().unwrap();
+
+        // The owner and the thief race for the only element through the CAS on `top`.
+        match (mine, theirs) {
+            (Some(1), Steal::Empty | Steal::Retry) | (None, Steal::Success(1)) => {}
+            other => panic!("element taken twice or lost: {other:?}"),
+        }
+        assert!(worker.is_empty());
+    });
+}
+
+#[test]
+fn deque_push_pop_steal() {
+    model(|| {
+        let (worker, stealer) = deque();
+        let thief = thread::spawn(move || {
+            let mut got = Vec::new();
+            for _ in 0..2 {
+                got.extend(stealer.steal().success());
+            }
+            got
+        });
+        worker.push(1);
+        worker.push(2);
+        let mut popped: Vec<_> = worker.pop().into_iter().collect();
+        popped.extend(worker.pop());
+        let stolen = thief.join().unwrap();
+
+        // Thieves take from the top, oldest first.
+        assert!(stolen.windows(2).all(|w| w[0] < w[1]));
+        popped.extend(stolen);
+        popped.sort_unstable();
+        assert_eq!(popped, [1, 2]);
+    });
+}
+
+/// Three pushes overflow the two-slot rings loom builds with unless the consumer keeps up,
+/// so it races the producer through a ring closing and the drain that retires it.
+#[test]
+fn faaqueue_push_pop_across_rings() {
+    model(|| {
+        let queue = Arc::new(FaaQueue::new());
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut popped = Vec::new();
+                for _ in 0..2 {
+                    popped.extend(queue.pop());
+                }
+                popped
+            })
+        };
+        for v in 1..=3 {
+            queue.push(v);
+        }
+
+        let mut popped = consumer.join().unwrap();
+        while let Some(v) = queue.pop() {
+            popped.push(v);
+        }
+        assert_eq!(popped, [1, 2, 3]);
+    });
+}
+
+#[test]
+fn queue_push_batch_pop_batch() {
+    model(|| {
+        let queue = Arc::new(Queue::new());
+        queue.push(1);
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.pop_batch(2))
+        };
+        queue.push_batch([2, 3]);
+
+        // The consumer got a prefix; the rest is still queued behind it, in order.
+        let mut popped = consumer.join().unwrap();
+        assert!(!popped.is_empty());
+        popped.extend(queue.pop_batch(3));
+        assert_eq!(popped, [1, 2, 3]);
+        assert!(queue.is_empty());
+    });
+}
+
+/// A push and a pop race on a one-element queue, where both ends meet at the dummy node.
+fn twolock_push_pop<L: RawLock + 'static>() {
+    model(|| {
+        let queue = Arc::new(TwoLockQueue::<_, L>::new());
+        queue.push(1);
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || [queue.pop(), queue.pop()])
+        };
+        queue.push(2);
+        let [first, second] = consumer.join().unwrap();
+
+        assert_eq!(first, Some(1));
+        let mut rest: Vec<_> = second.into_iter().collect();
+        rest.extend(queue.pop());
+        assert_eq!(rest, [2]);
+        assert!(queue.is_empty());
+    });
+}
+
+#[test]
+fn twolock_ticket_push_pop() {
+    twolock_push_pop::<TicketLock>();
+}
+
+#[test]
+fn twolock_mcs_push_pop() {
+    twolock_push_pop::<McsLock>();
+}
+
+fn rendezvous_put_take(new: fn() -> SynchronousQueue<i32>) {
+    model(move || {
+        let queue = Arc::new(new());
+        let taker = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.take())
+        };
+        // loom reports a deadlock if either side can park after missing the other.
+        queue.put(1);
+        assert_eq!(taker.join().unwrap(), 1);
+    });
+}
+
+#[test]
+fn rendezvous_fair_put_take() {
+    rendezvous_put_take(SynchronousQueue::fair);
+}
+
+#[test]
+fn rendezvous_unfair_put_take() {
+    rendezvous_put_take(SynchronousQueue::unfair);
+}
+
+#[test]
+fn rendezvous_try_put_meets_take() {
+    model(|| {
+        let queue = Arc::new(SynchronousQueue::unfair());
+        let taker = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.take())
+        };
+        // `try_put` never waits, so it only succeeds once the taker is parked on the stack.
+        let mut item = 1;
+        while let Err(back) = queue.try_put(item) {
+            item = back;
+            thread::yield_now();
+        }
+        assert_eq!(taker.join().unwrap(), 1);
+        assert_eq!(queue.try_take(), None);
+    });
+}
+
+/// One slot forces the second send to wait for the subscriber to read the first.
+#[test]
+fn broadcast_backpressure_handoff() {
+    model(|| {
+        let (sender, mut receiver) = broadcast::channel(1, Mode::Backpressure);
+        let handle = thread::spawn(move || {
+            let got = [receiver.recv(), receiver.recv()];
+            (got, receiver.recv())
+        });
+        sender.send(1).unwrap();
+        sender.send(2).unwrap();
+        drop(sender);
+        let (got, end) = handle.join().unwrap();
+        assert_eq!(got, [Ok(1), Ok(2)]);
+        assert_eq!(end, Err(RecvError::Closed));
+    });
+}
+
+#[test]
+fn asyncqueue_pop_wakeup() {
+    model(|| {
+        let queue = Arc::new(AsyncQueue::new());
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || loom::future::block_on(queue.pop()))
+        };
+        queue.push(1);
+        // loom reports a deadlock if the pop can return `Pending` after missing the push.
+        assert_eq!(consumer.join().unwrap(), 1);
+        assert!(queue.is_empty());
+    });
+}
+
+#[test]
+fn queue_pop_blocking_wakeup() {
+    model(|| {
+        let queue = Arc::new(Queue::new());
+        let consumer = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || queue.pop_blocking())
+        };
+        queue.push(1);
+        // loom reports a deadlock if the consumer can park after missing the push's wakeup.
+        assert_eq!(consumer.join().unwrap(), 1);
+    });
+}
+
+#[test]
+fn select_ready_wakeup() {
+    model(|| {
+        let queue = Arc::new(Queue::new());
+        let selector = {
+            let queue = Arc::clone(&queue);
+            thread::spawn(move || {
+                let mut sel = Select::new();
+                sel.recv(&*queue);
+                assert_eq!(sel.ready(), 0);
+                queue.pop()
+            })
+        };
+        queue.push(1);
+        // loom reports a deadlock if the selector can park after its re-check missed the push
+        // and the push missed its signal.
+        assert_eq!(selector.join().unwrap(), Some(1));
+    });
+}
+
+#[test]
+fn select_disconnect_wakeup() {
+    model(|| {
+        let (sender, receiver) = channel::unbounded::<i32>();
+        let handle = thread::spawn(move || drop(sender));
+        let got = crate::select! {
+            recv(receiver) -> msg => msg,
+        };
+        assert_eq!(got, Err(channel::RecvError));
+        handle.join().unwrap();
+    });
+}
+
+#[test]
+fn list_insert_same_key() {
+    model(|| {
+        let list = Arc::new(List::new());
+        let other = Arc::clone(&list);
+        let handle = thread::spawn(move || other.harris_michael_insert(1, 10, &pin()));
+        let mine = list.harris_michael_insert(1, 20, &pin());
+        let theirs = handle.join().unwrap();
+
+        assert!(mine ^ theirs, "exactly one insert of a key may succeed");
+        let expected = if mine { 20 } else { 10 };
+        assert_eq!(list.harris_michael_lookup(&1, &mut pin()), Some(&expected));
+    });
+}
+
+#[test]
+fn list_insert_delete() {
+    model(|| {
+        let list = Arc::new(List::new());
+        list.harris_michael_insert(1, 10, &pin());
+        let other = Arc::clone(&list);
+        let handle = thread::spawn(move || other.harris_michael_delete(&1, &mut pin()).copied());
+        let inserted = list.harris_michael_insert(2, 20, &pin());
+        let deleted = handle.join().unwrap();
+
+        let mut guard = pin();
+        assert!(inserted);
+        assert_eq!(deleted, Some(10));
+        assert_eq!(list.harris_michael_lookup(&1, &mut guard), None);
+        assert_eq!(list.harris_michael_lookup(&2, &mut guard), Some(&20));
+    });
+}
@@ -1,5 +1,18 @@
-use prac::lockfreelist;
+use std::{env, process};
+
+use prac::bench::{self, Config};
 
 fn main() {
-    lockfreelist::lc();
+    let cfg = match Config::from_args(env::args().skip(1)) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{}", bench::USAGE);
+            process::exit(2);
+        }
+    };
+    let reports = bench::run_all(&cfg).unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        process::exit(1);
+    });
+    print!("{}", bench::render(&reports, cfg.format));
 }
@@ -1,25 +1,25 @@
-use std::{
-    ptr::null_mut,
+use std::ptr::null_mut;
+
+use crossbeam_utils::CachePadded;
+
+use crate::{
+    rawlock::RawLock,
     sync::{
-        Arc,
-        atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::*},
+        atomic::{AtomicBool, AtomicPtr, Ordering::*},
+        hint, thread,
     },
-    thread::{self},
-    time::Instant,
 };
 
-use crossbeam::utils::CachePadded;
-
 struct Node {
     locked: AtomicBool,
     next: AtomicPtr<CachePadded<Node>>,
 }
 
-struct McsLock {
+pub struct McsLock {
     tail: AtomicPtr<CachePadded<Node>>,
 }
 
-struct Token(*mut CachePadded<Node>);
+pub struct Token(*mut CachePadded<Node>);
 
 impl Node {
     pub fn new(lock: bool) -> *mut CachePadded<Node> {
@@ -39,7 +39,9 @@ impl McsLock {
 
     pub fn lock(&self) -> Token {
         let node = Node::new(true);
-        let prev = self.tail.swap(node, Relaxed);
+        // Acquire pairs with the releasing CAS in `unlock` when the queue was empty, so the
+        // previous critical section happens-before ours; Release publishes our node.
+        let prev = self.tail.swap(node, AcqRel);
         if prev.is_null() {
             return Token(node);
         }
@@ -47,7 +49,7 @@ impl McsLock {
         unsafe {
             (*prev).next.store(node, Release);
             while (*node).locked.load(Acquire) {
-                std::hint::spin_loop();
+                hint::spin_loop();
             }
         }
 
@@ -70,7 +72,7 @@ impl McsLock {
                 next = unsafe { (*node).next.load(Acquire) };
                 next.is_null()
             } {
-                std::thread::yield_now();
+                thread::yield_now();
             }
         }
 
@@ -81,29 +83,20 @@ impl McsLock {
     }
 }
 
-pub fn mcslock() {
-    let lock = Arc::new(McsLock::new());
-    let counter = Arc::new(AtomicUsize::new(0));
-    let mut handles = Vec::new();
-    let start = Instant::now();
-
-    for _ in 0..8 {
-        let lock = Arc::clone(&lock);
-        let counter = Arc::clone(&counter);
-        handles.push(thread::spawn(move || {
-            for _ in 0..100 {
-                let token = lock.lock();
-                counter.fetch_add(1, Relaxed);
-                lock.unlock(token);
-            }
-        }));
+impl Default for McsLock {
+    fn default() -> Self {
+        Self::new()
     }
+}
+
+impl RawLock for McsLock {
+    type Token = Token;
 
-    for handle in handles {
-        handle.join().unwrap();
+    fn lock(&self) -> Token {
+        McsLock::lock(self)
     }
-    let duration = start.elapsed();
 
-    println!("Expected: {} completed at {:?}", 8 * 100, duration);
-    println!("Actual: {}", counter.load(Relaxed));
+    fn unlock(&self, token: Token) {
+        McsLock::unlock(self, token)
+    }
 }
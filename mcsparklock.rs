@@ -1,16 +1,17 @@
-use std::{
-    ptr::null_mut,
+use std::{ptr::null_mut, sync::Arc};
+
+use crossbeam_utils::CachePadded;
+
+use crate::{
+    rawlock::RawLock,
     sync::{
-        Arc,
-        atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering::*},
+        atomic::{AtomicBool, AtomicPtr, Ordering::*},
+        hint,
+        thread::{self, Thread},
     },
-    thread::{self, Thread},
-    time::Instant,
 };
 
-use crossbeam::utils::CachePadded;
-
-struct McsParkLock {
+pub struct McsParkLock {
     tail: AtomicPtr<CachePadded<Node>>,
 }
 
@@ -30,7 +31,7 @@ impl Node {
     }
 }
 
-struct Token(*mut CachePadded<Node>);
+pub struct Token(*mut CachePadded<Node>);
 
 impl McsParkLock {
     pub fn new() -> McsParkLock {
@@ -41,7 +42,9 @@ impl McsParkLock {
 
     pub fn lock(&self) -> Token {
         let node = Node::new(true);
-        let prev = self.tail.swap(node, Relaxed);
+        // Acquire pairs with the releasing CAS in `unlock` when the queue was empty, so the
+        // previous critical section happens-before ours; Release publishes our node.
+        let prev = self.tail.swap(node, AcqRel);
         if prev.is_null() {
             return Token(node);
         }
@@ -75,7 +78,9 @@ impl McsParkLock {
             while {
                 next = unsafe { (*node).next.load(Acquire) };
                 next.is_null()
-            } {}
+            } {
+                hint::spin_loop();
+            }
         }
         unsafe {
             let next_ref = &*next;
@@ -87,30 +92,20 @@ impl McsParkLock {
     }
 }
 
-pub fn mcsparklock() {
-    let lock = Arc::new(McsParkLock::new());
-    let counter = Arc::new(AtomicUsize::new(0));
-    let mut handles = Vec::new();
-
-    for _ in 0..8 {
-        let lock = Arc::clone(&lock);
-        let counter = Arc::clone(&counter);
-        handles.push(thread::spawn(move || {
-            for _ in 0..100 {
-                let token = lock.lock();
-                counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                lock.unlock(token);
-            }
-        }));
+impl Default for McsParkLock {
+    fn default() -> Self {
+        Self::new()
     }
-    let start = Instant::now();
-    for handle in handles {
-        handle.join().unwrap();
+}
+
+impl RawLock for McsParkLock {
+    type Token = Token;
+
+    fn lock(&self) -> Token {
+        McsParkLock::lock(self)
+    }
+
+    fn unlock(&self, token: Token) {
+        McsParkLock::unlock(self, token)
     }
-    let duration = start.elapsed();
-    println!("Expected: {} completed in {:?}", 8 * 100, duration);
-    println!(
-        "Actual: {}",
-        counter.load(std::sync::atomic::Ordering::Relaxed)
-    );
 }
@@ -1,136 +1,488 @@
 use std::{
-    mem::{self, MaybeUninit},
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ptr,
     sync::Arc,
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use crossbeam::utils::CachePadded;
-use crossbeam_epoch::{Atomic, Guard, Owned, Shared, pin};
+use crossbeam_epoch::pin;
+use crossbeam_utils::CachePadded;
 
-pub struct Queue<T> {
-    head: CachePadded<Atomic<Node<T>>>,
-    tail: CachePadded<Atomic<Node<T>>>,
+use crate::{
+    reclaim::{Epoch, Optimistic, Reclaimer},
+    select::Signal,
+    sync::{
+        Condvar, Mutex,
+        atomic::{AtomicPtr, AtomicUsize, Ordering, fence},
+    },
+};
+
+pub struct Queue<T, R: Reclaimer = Epoch> {
+    head: CachePadded<AtomicPtr<Node<T>>>,
+    tail: CachePadded<AtomicPtr<Node<T>>>,
+    /// Consumers parked (or about to park) in `pop_blocking`/`pop_timeout`, plus selectors
+    /// watching this queue. `push` only takes `waiters` when this is non-zero, so the lock
+    /// stays off the fast path.
+    sleepers: AtomicUsize,
+    /// Signals of the [`Select`](crate::select::Select)s currently watching this queue.
+    waiters: Mutex<Vec<Arc<Signal>>>,
+    ready: Condvar,
+    _reclaimer: PhantomData<R>,
 }
 
 pub struct Node<T> {
     data: MaybeUninit<T>,
-    next: Atomic<Node<T>>,
+    next: AtomicPtr<Node<T>>,
 }
 
-unsafe impl<T> Sync for Queue<T> where T: Send {}
-unsafe impl<T> Send for Queue<T> where T: Send {}
+impl<T> Node<T> {
+    fn new(data: MaybeUninit<T>) -> *mut Self {
+        Box::into_raw(Box::new(Node {
+            data,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
 
-impl<T> Default for Queue<T> {
+unsafe impl<T, R: Reclaimer> Sync for Queue<T, R> where T: Send {}
+unsafe impl<T, R: Reclaimer> Send for Queue<T, R> where T: Send {}
+
+impl<T, R: Reclaimer> Default for Queue<T, R> {
     fn default() -> Self {
-        Self::new()
+        Self::with_reclaimer()
     }
 }
 
 impl<T> Queue<T> {
     pub fn new() -> Self {
-        let sentinel = Box::into_raw(Box::new(Node {
-            data: MaybeUninit::uninit(),
-            next: Atomic::null(),
-        }))
-        .cast_const();
+        Self::with_reclaimer()
+    }
+}
+
+impl<T, R: Reclaimer> Queue<T, R> {
+    /// Creates a queue that frees popped nodes through `R` instead of the default epoch.
+    pub fn with_reclaimer() -> Self {
+        let sentinel = Node::new(MaybeUninit::uninit());
         Self {
-            head: CachePadded::new(sentinel.into()),
-            tail: CachePadded::new(sentinel.into()),
+            head: CachePadded::new(AtomicPtr::new(sentinel)),
+            tail: CachePadded::new(AtomicPtr::new(sentinel)),
+            sleepers: AtomicUsize::new(0),
+            waiters: Mutex::new(Vec::new()),
+            ready: Condvar::new(),
+            _reclaimer: PhantomData,
         }
     }
-    pub fn push(&self, t: T, guard: &mut Guard) {
-        let mut node = Owned::new(Node {
-            data: MaybeUninit::new(t),
-            next: Atomic::null(),
-        });
+    /// Pushes `t` onto the tail, holding a guard for the duration of the call.
+    pub fn push(&self, t: T) {
+        self.push_with_guard(t, &mut R::pin());
+    }
+
+    /// Pops from the head, holding a guard for the duration of the call.
+    pub fn pop(&self) -> Option<T> {
+        self.pop_with_guard(&mut R::pin())
+    }
+
+    /// Pops from the head, parking the current thread until an element arrives.
+    pub fn pop_blocking(&self) -> T {
+        self.pop_until(None, || false)
+            .expect("pop without a deadline only returns with an element")
+    }
+
+    /// Pops from the head, parking for at most `timeout` while the queue is empty.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        self.pop_until(Some(Instant::now() + timeout), || false)
+    }
+
+    /// Parks until an element arrives, `deadline` passes, or `closed` reports that no more
+    /// pushes will come. Whoever makes `closed` true must call [`Queue::wake_all`] afterwards.
+    pub(crate) fn pop_until<F>(&self, deadline: Option<Instant>, closed: F) -> Option<T>
+    where
+        F: Fn() -> bool,
+    {
+        loop {
+            if let Some(t) = self.pop() {
+                return Some(t);
+            }
+            let remaining = match deadline {
+                Some(d) => match d.checked_duration_since(Instant::now()) {
+                    Some(r) if !r.is_zero() => Some(r),
+                    _ => return None,
+                },
+                None => None,
+            };
+            let waiters = self.waiters.lock().unwrap();
+            // Pairs with the fence in `push_with_guard`: either that push sees us counted here
+            // and notifies, or our re-check below sees its element.
+            self.sleepers.fetch_add(1, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+            if let Some(t) = self.pop() {
+                self.sleepers.fetch_sub(1, Ordering::SeqCst);
+                return Some(t);
+            }
+            if closed() {
+                self.sleepers.fetch_sub(1, Ordering::SeqCst);
+                // A push that raced with the close is visible now that we have seen it closed.
+                return self.pop();
+            }
+            let waiters = match remaining {
+                Some(r) => self.ready.wait_timeout(waiters, r).unwrap().0,
+                None => self.ready.wait(waiters).unwrap(),
+            };
+            self.sleepers.fetch_sub(1, Ordering::SeqCst);
+            drop(waiters);
+        }
+    }
+
+    /// Wakes every consumer parked in `pop_until`, and every watching selector, so it can
+    /// re-check its exit conditions.
+    pub(crate) fn wake_all(&self) {
+        let waiters = self.waiters.lock().unwrap();
+        self.ready.notify_all();
+        waiters.iter().for_each(|s| s.fire());
+    }
+
+    /// Registers `signal` to be fired by every push until [`Queue::unwatch`]. Counts as a
+    /// sleeper, with the same fence as `pop_until`, so a push that the caller's re-check
+    /// misses is guaranteed to fire it.
+    pub(crate) fn watch(&self, signal: &Arc<Signal>) {
+        let mut waiters = self.waiters.lock().unwrap();
+        waiters.push(Arc::clone(signal));
+        self.sleepers.fetch_add(1, Ordering::SeqCst);
+        fence(Ordering::SeqCst);
+    }
+
+    pub(crate) fn unwatch(&self, signal: &Arc<Signal>) {
+        let mut waiters = self.waiters.lock().unwrap();
+        if let Some(i) = waiters.iter().position(|s| Arc::ptr_eq(s, signal)) {
+            waiters.swap_remove(i);
+            self.sleepers.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let guard = R::pin();
+        let head = R::protect(&guard, 0, &self.head);
+        unsafe { &*head }.next.load(Ordering::Acquire).is_null()
+    }
+
+    /// Returns the element at the head without removing it.
+    ///
+    /// Limited to `T: Copy` because a concurrent `pop` moves the value out of the node while
+    /// the reference is live, and its new owner is free to drop it; only types without drop
+    /// glue stay valid behind the reference until `guard` is dropped.
+    ///
+    /// Takes the guard mutably: the reference is protected by one of the guard's slots, which
+    /// the next operation on the guard would reuse.
+    pub fn peek<'g>(&self, guard: &'g mut R::Guard) -> Option<&'g T>
+    where
+        T: Copy,
+    {
         loop {
-            let tail = self.tail.load(std::sync::atomic::Ordering::Acquire, guard);
-            let tail_ref = unsafe { tail.deref() };
-            let next = tail_ref
+            let head = R::protect(guard, 0, &self.head);
+            let next = R::protect(guard, 1, &unsafe { &*head }.next);
+            // `next` is only retired once `head` has moved past it, so while `head` stays put
+            // the protection above was taken in time.
+            if self.head.load(Ordering::Acquire) == head {
+                return unsafe { next.as_ref() }.map(|n| unsafe { n.data.assume_init_ref() });
+            }
+            R::repin(guard);
+        }
+    }
+
+    /// Pops the head element only if `predicate` accepts it; `T: Copy` for the same reason as
+    /// [`Queue::peek`].
+    pub fn try_pop_if<F>(&self, predicate: F) -> Option<T>
+    where
+        T: Copy,
+        F: Fn(&T) -> bool,
+    {
+        self.pop_if(
+            |data| predicate(unsafe { data.assume_init_ref() }),
+            &mut R::pin(),
+        )
+    }
+
+    /// Iterates from the head without removing anything.
+    ///
+    /// Only a snapshot under concurrent use: elements popped meanwhile may still be yielded,
+    /// and elements pushed meanwhile are yielded only if they land before the iterator
+    /// reaches the tail. `T: Copy` for the same reason as [`Queue::peek`].
+    pub fn iter<'g>(&self, guard: &'g R::Guard) -> Iter<'g, T, R>
+    where
+        T: Copy,
+        R: Optimistic,
+    {
+        let head = R::protect(guard, 0, &self.head);
+        Iter {
+            curr: R::protect(guard, 1, &unsafe { &*head }.next),
+            guard,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Pops elements from the head until the queue is found empty.
+    pub fn drain(&self) -> Drain<'_, T, R> {
+        Drain { queue: self }
+    }
+
+    /// Moves `tail` forward if it still lags at `tail`, as every operation may.
+    fn help_tail(&self, tail: *mut Node<T>, next: *mut Node<T>) {
+        let _ = self
+            .tail
+            .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+    }
+
+    /// Wakes a consumer parked in `pop_until` and the watching selectors after a push of
+    /// `len` elements, if anyone is waiting.
+    fn notify_push(&self, len: usize) {
+        fence(Ordering::SeqCst);
+        if self.sleepers.load(Ordering::Relaxed) > 0 {
+            let waiters = self.waiters.lock().unwrap();
+            if len == 1 {
+                self.ready.notify_one();
+            } else {
+                self.ready.notify_all();
+            }
+            waiters.iter().for_each(|s| s.fire());
+        }
+    }
+
+    pub fn push_with_guard(&self, t: T, guard: &mut R::Guard) {
+        let node = Node::new(MaybeUninit::new(t));
+        loop {
+            // A node is only retired once `head` has passed it, which needs `tail` to have
+            // moved on first, so `tail` is safe to use as long as it is still the tail.
+            let tail = R::protect(guard, 0, &self.tail);
+            let tail_ref = unsafe { &*tail };
+            let next = tail_ref.next.load(Ordering::Acquire);
+            if !next.is_null() {
+                self.help_tail(tail, next);
+            } else if tail_ref
                 .next
-                .load(std::sync::atomic::Ordering::Acquire, guard);
+                .compare_exchange(ptr::null_mut(), node, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, node, Ordering::Release, Ordering::Acquire);
+                self.notify_push(1);
+                return;
+            }
+            R::repin(guard);
+        }
+    }
+
+    /// Pushes every element of `iter`, in order, as one contiguous run: the nodes are linked
+    /// privately first and spliced onto the tail with a single CAS, so concurrent pushes
+    /// never interleave with the batch.
+    pub fn push_batch<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let mut nodes = iter.into_iter().map(|t| Node::new(MaybeUninit::new(t)));
+        let Some(first) = nodes.next() else {
+            return;
+        };
+        let mut last = first;
+        let mut len = 1;
+        for node in nodes {
+            unsafe { &*last }.next.store(node, Ordering::Relaxed);
+            last = node;
+            len += 1;
+        }
+        let mut guard = R::pin();
+        loop {
+            let tail = R::protect(&guard, 0, &self.tail);
+            let tail_ref = unsafe { &*tail };
+            let next = tail_ref.next.load(Ordering::Acquire);
             if !next.is_null() {
-                let _ = self.tail.compare_exchange(
-                    tail,
-                    next,
-                    std::sync::atomic::Ordering::Release,
-                    std::sync::atomic::Ordering::Relaxed,
-                    guard,
-                );
-                continue;
+                self.help_tail(tail, next);
+            } else if tail_ref
+                .next
+                .compare_exchange(ptr::null_mut(), first, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, last, Ordering::Release, Ordering::Acquire);
+                self.notify_push(len);
+                return;
             }
-            match tail_ref.next.compare_exchange(
-                Shared::null(),
-                node,
-                std::sync::atomic::Ordering::Release,
-                std::sync::atomic::Ordering::Relaxed,
-                guard,
-            ) {
-                Ok(new) => {
-                    let _ = self.tail.compare_exchange(
-                        tail,
-                        new,
-                        std::sync::atomic::Ordering::Release,
-                        std::sync::atomic::Ordering::Acquire,
-                        guard,
-                    );
+            R::repin(&mut guard);
+        }
+    }
+
+    /// Pops up to `n` elements from the head, oldest first, by swinging `head` past all of
+    /// them with a single CAS.
+    pub fn pop_batch(&self, n: usize) -> Vec<T> {
+        self.pop_batch_with_guard(n, &mut R::pin())
+    }
+
+    pub fn pop_batch_with_guard(&self, n: usize, guard: &mut R::Guard) -> Vec<T> {
+        'retry: loop {
+            R::repin(guard);
+            let head = R::protect(guard, 0, &self.head);
+            let mut taken = Vec::new();
+            let mut last = head;
+            while taken.len() < n {
+                // Hand over hand: `last` sits in slot 1 or 2 and `next` goes into the other.
+                // The nodes left behind need no slot of their own, since as in `peek` nothing
+                // past `head` is retired while `head` stays put.
+                let next = R::protect(guard, 1 + taken.len() % 2, &unsafe { &*last }.next);
+                if self.head.load(Ordering::Acquire) != head {
+                    continue 'retry;
+                }
+                if next.is_null() {
                     break;
                 }
-                Err(e) => node = e.new,
+                taken.push(next);
+                last = next;
+            }
+            if taken.is_empty() {
+                return Vec::new();
+            }
+            // `tail` must not be left on a node we are about to retire: help it past `last`
+            // and start over.
+            let tail = self.tail.load(Ordering::Acquire);
+            if tail == head || taken[..taken.len() - 1].contains(&tail) {
+                self.help_tail(tail, last);
+                continue;
+            }
+            if self
+                .head
+                .compare_exchange(head, last, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                // `last` stays behind as the new sentinel; everything before it is ours.
+                let out = taken
+                    .iter()
+                    .map(|&node| unsafe { (*node).data.assume_init_read() })
+                    .collect();
+                unsafe {
+                    R::retire(guard, head);
+                    for &node in &taken[..taken.len() - 1] {
+                        R::retire(guard, node);
+                    }
+                }
+                return out;
             }
-            guard.repin();
         }
     }
-    pub fn pop(&self, guard: &mut Guard) -> Option<T> {
+
+    pub fn pop_with_guard(&self, guard: &mut R::Guard) -> Option<T> {
+        self.pop_if(|_| true, guard)
+    }
+
+    fn pop_if<F>(&self, predicate: F, guard: &mut R::Guard) -> Option<T>
+    where
+        F: Fn(&MaybeUninit<T>) -> bool,
+    {
         loop {
-            let head = self.head.load(std::sync::atomic::Ordering::Acquire, guard);
-            let next = unsafe { head.as_ref()? }
-                .next
-                .load(std::sync::atomic::Ordering::Acquire, &guard);
+            let head = R::protect(guard, 0, &self.head);
+            let next = R::protect(guard, 1, &unsafe { &*head }.next);
+            if self.head.load(Ordering::Acquire) != head {
+                R::repin(guard);
+                continue;
+            }
             let next_ref = unsafe { next.as_ref()? };
-            let tail = self.tail.load(std::sync::atomic::Ordering::Acquire, guard);
+            if !predicate(&next_ref.data) {
+                return None;
+            }
+            let tail = self.tail.load(Ordering::Acquire);
             if tail == head {
-                let _ = self.tail.compare_exchange(
-                    tail,
-                    next,
-                    std::sync::atomic::Ordering::Release,
-                    std::sync::atomic::Ordering::Relaxed,
-                    guard,
-                );
+                self.help_tail(tail, next);
             }
             if self
                 .head
-                .compare_exchange(
-                    head,
-                    next,
-                    std::sync::atomic::Ordering::Release,
-                    std::sync::atomic::Ordering::Relaxed,
-                    guard,
-                )
+                .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed)
                 .is_ok()
             {
                 let result = unsafe { next_ref.data.assume_init_read() };
-                unsafe {
-                    guard.defer_destroy(head);
-                };
+                unsafe { R::retire(guard, head) };
                 return Some(result);
             }
-            guard.repin();
+            R::repin(guard);
         }
     }
 }
 
-impl<T> Drop for Queue<T> {
+impl<T, R: Reclaimer> Drop for Queue<T, R> {
     fn drop(&mut self) {
-        let sentinel = mem::take(&mut *self.head);
-        let mut o_curr = unsafe { sentinel.into_owned() }.into_box().next;
-        while let Some(curr) = unsafe { o_curr.try_into_owned() }.map(|s| s.into_box()) {
-            drop(unsafe { curr.data.assume_init() });
-            o_curr = curr.next;
+        let sentinel = unsafe { Box::from_raw(self.head.load(Ordering::Relaxed)) };
+        let mut curr = sentinel.next.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            drop(unsafe { node.data.assume_init() });
+            curr = node.next.load(Ordering::Relaxed);
         }
     }
 }
 
+pub struct Iter<'g, T, R: Reclaimer = Epoch> {
+    curr: *mut Node<T>,
+    guard: &'g R::Guard,
+    _marker: PhantomData<&'g T>,
+}
+
+impl<'g, T: Copy, R: Optimistic> Iterator for Iter<'g, T, R> {
+    type Item = &'g T;
+
+    fn next(&mut self) -> Option<&'g T> {
+        let node = unsafe { self.curr.as_ref()? };
+        // Reusing the slot is fine: under `R: Optimistic` every node reached stays allocated.
+        self.curr = R::protect(self.guard, 1, &node.next);
+        Some(unsafe { node.data.assume_init_ref() })
+    }
+}
+
+pub struct Drain<'a, T, R: Reclaimer = Epoch> {
+    queue: &'a Queue<T, R>,
+}
+
+impl<T, R: Reclaimer> Iterator for Drain<'_, T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.pop()
+    }
+}
+
+/// Pops the elements in FIFO order; whatever is left is dropped with the iterator.
+pub struct IntoIter<T, R: Reclaimer = Epoch> {
+    queue: Queue<T, R>,
+}
+
+impl<T, R: Reclaimer> Iterator for IntoIter<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.queue.pop()
+    }
+}
+
+impl<T, R: Reclaimer> IntoIterator for Queue<T, R> {
+    type Item = T;
+    type IntoIter = IntoIter<T, R>;
+
+    fn into_iter(self) -> IntoIter<T, R> {
+        IntoIter { queue: self }
+    }
+}
+
+impl<T, R: Reclaimer> FromIterator<T> for Queue<T, R> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let queue = Queue::with_reclaimer();
+        queue.push_batch(iter);
+        queue
+    }
+}
+
+/// Appends all of `iter` with a single CAS through [`Queue::push_batch`].
+impl<T, R: Reclaimer> Extend<T> for Queue<T, R> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.push_batch(iter);
+    }
+}
+
 pub fn q() {
     let a: Arc<Queue<u32>> = Arc::new(Queue::default());
     let mut handles: Vec<JoinHandle<()>> = Vec::new();
@@ -139,7 +491,7 @@ pub fn q() {
         handles.push(thread::spawn(move || {
             let mut guard = pin();
             for j in 0..100 {
-                a.push(i * 100 + j, &mut guard);
+                a.push_with_guard(i * 100 + j, &mut guard);
             }
         }));
     }
@@ -147,13 +499,8 @@ pub fn q() {
     for _ in 0..5 {
         let a = Arc::clone(&a);
         handles.push(thread::spawn(move || {
-            let mut guard = pin();
-            let mut local_count = 0;
-            while local_count < 200 {
-                if let Some(val) = a.pop(&mut guard) {
-                    println!("Popped:{}", val);
-                    local_count += 1;
-                }
+            for _ in 0..200 {
+                println!("Popped:{}", a.pop_blocking());
             }
         }));
     }
@@ -161,3 +508,110 @@ pub fn q() {
         h.join().unwrap();
     }
 }
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn peek_leaves_the_head_in_place() {
+        let queue = Queue::new();
+        let mut guard = pin();
+        assert_eq!(queue.peek(&mut guard), None);
+        queue.push(1);
+        queue.push(2);
+        assert_eq!(queue.peek(&mut guard), Some(&1));
+        assert_eq!(queue.peek(&mut guard), Some(&1));
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.peek(&mut guard), Some(&2));
+        assert_eq!(queue.pop(), Some(2));
+        assert_eq!(queue.peek(&mut guard), None);
+    }
+
+    #[test]
+    fn try_pop_if_only_takes_accepted_heads() {
+        let queue: Queue<i32> = (1..=3).collect();
+        assert_eq!(queue.try_pop_if(|&v| v > 1), None);
+        assert_eq!(queue.peek(&mut pin()), Some(&1));
+        assert_eq!(queue.try_pop_if(|&v| v == 1), Some(1));
+        // The predicate only ever sees the head, never what comes after it.
+        assert_eq!(queue.try_pop_if(|&v| v == 3), None);
+        assert_eq!(queue.into_iter().collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(Queue::<i32>::new().try_pop_if(|_| true), None);
+    }
+
+    #[test]
+    fn pop_timeout_gives_up_on_an_empty_queue() {
+        let queue = Queue::<i32>::new();
+        let start = Instant::now();
+        assert_eq!(queue.pop_timeout(Duration::from_millis(50)), None);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(queue.pop_timeout(Duration::ZERO), None);
+        queue.push(1);
+        assert_eq!(queue.pop_timeout(Duration::ZERO), Some(1));
+    }
+
+    #[test]
+    fn pop_timeout_wakes_on_push() {
+        let queue = Queue::new();
+        let start = Instant::now();
+        let popped = thread::scope(|s| {
+            let waiter = s.spawn(|| queue.pop_timeout(Duration::from_secs(60)));
+            thread::sleep(Duration::from_millis(50));
+            queue.push(7);
+            waiter.join().unwrap()
+        });
+        assert_eq!(popped, Some(7));
+        assert!(
+            start.elapsed() < Duration::from_secs(30),
+            "waiter slept through the push"
+        );
+    }
+
+    #[test]
+    fn iterators_collect_drain_and_snapshot() {
+        let mut queue: Queue<_> = (0..5).collect();
+        queue.extend(5..10);
+        assert_eq!(
+            queue.iter(&pin()).copied().collect::<Vec<_>>(),
+            (0..10).collect::<Vec<_>>()
+        );
+        assert_eq!(queue.drain().take(3).collect::<Vec<_>>(), [0, 1, 2]);
+        assert_eq!(
+            queue.into_iter().collect::<Vec<_>>(),
+            (3..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn into_iter_drops_the_rest() {
+        let counts = DropCounts::new(10);
+        let mut it = (0..10)
+            .map(|v| counts.track(v))
+            .collect::<Queue<_>>()
+            .into_iter();
+        assert_eq!(it.next().map(|t| t.value), Some(0));
+        drop(it);
+        counts.assert_all_dropped();
+    }
+
+    #[test]
+    fn pop_batch_walks_with_three_slots() {
+        use crate::{hazard::Hazard, reclaim::Reclaimer};
+
+        let queue = Queue::<_, Hazard>::with_reclaimer();
+        queue.push_batch(0..100);
+        let mut guard = Hazard::pin();
+        assert_eq!(
+            queue.pop_batch_with_guard(60, &mut guard),
+            (0..60).collect::<Vec<_>>()
+        );
+        assert_eq!(guard.slots(), 3);
+        assert_eq!(
+            queue.pop_batch_with_guard(60, &mut guard),
+            (60..100).collect::<Vec<_>>()
+        );
+        assert_eq!(guard.slots(), 3);
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    cell::{Cell, RefCell},
+    marker::PhantomData,
+    mem, ptr,
+};
+
+#[cfg(not(loom))]
+use crate::reclaim::{Optimistic, Reclaimer};
+use crate::sync::{
+    Mutex,
+    atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering, fence},
+};
+
+/// Retired pointers a registration lets pile up before a `retire` tries to free them.
+#[cfg(not(loom))]
+const THRESHOLD: usize = 128;
+#[cfg(loom)]
+const THRESHOLD: usize = 1;
+
+/// The quiescent-state counter of one registered thread.
+struct Record {
+    /// The epoch the thread last announced a quiescent state in, or 0 while offline.
+    ctr: AtomicU64,
+    /// Owned by a [`Registration`].
+    active: AtomicBool,
+    /// Records are only ever prepended, and live as long as their domain.
+    next: *const Record,
+}
+
+struct Retired {
+    /// Freed once every online thread has announced an epoch at least this high.
+    epoch: u64,
+    ptr: *mut (),
+    drop: unsafe fn(*mut ()),
+}
+
+unsafe fn drop_box<T>(ptr: *mut ()) {
+    drop(unsafe { Box::from_raw(ptr.cast::<T>()) });
+}
+
+impl Retired {
+    unsafe fn free(self) {
+        unsafe { (self.drop)(self.ptr) };
+    }
+}
+
+/// A quiescent-state-based reclamation domain (McKenney and Slingwine).
+///
+/// Registered threads announce, at points where they hold no reference into any structure,
+/// that they are quiescent; a retired pointer is freed once every online thread has done so
+/// since it was retired. Reads carry no cost at all: there is no pin, no fence and no
+/// shared write on the read side, only a counter store at each quiescent point. The price is
+/// that a registered thread that stops announcing holds up all reclamation, so a thread that
+/// blocks for long should go [offline](Registration::offline) first.
+pub struct Domain {
+    /// Bumped by every retire; the retired pointer is tagged with the new value.
+    epoch: AtomicU64,
+    records: AtomicPtr<Record>,
+    /// Retired pointers left behind by registrations dropped before their grace period ended.
+    orphans: Mutex<Vec<Retired>>,
+}
+
+unsafe impl Send for Domain {}
+unsafe impl Sync for Domain {}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Domain {
+    pub fn new() -> Self {
+        Self {
+            epoch: AtomicU64::new(1),
+            records: AtomicPtr::new(ptr::null_mut()),
+            orphans: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The process-wide domain behind the [`Qsbr`] reclaimer.
+    #[cfg(not(loom))]
+    pub fn global() -> &'static Domain {
+        static GLOBAL: std::sync::OnceLock<Domain> = std::sync::OnceLock::new();
+        GLOBAL.get_or_init(Domain::new)
+    }
+
+    /// Registers the calling thread, which is online from now on: until it goes offline or
+    /// drops the registration, nothing retired after this point is freed before the
+    /// thread's next [`quiescent`](Registration::quiescent).
+    pub fn register(&self) -> Registration<'_> {
+        let record = self.acquire();
+        let registration = Registration {
+            domain: self,
+            record,
+            limbo: RefCell::new(Vec::new()),
+            guards: Cell::new(0),
+            _not_send: PhantomData,
+        };
+        registration.online();
+        registration
+    }
+
+    /// Claims an inactive record, or links a new one.
+    fn acquire(&self) -> &Record {
+        let mut curr = self.records.load(Ordering::Acquire).cast_const();
+        while let Some(record) = unsafe { curr.as_ref() } {
+            if !record.active.load(Ordering::Relaxed)
+                && record
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            {
+                return record;
+            }
+            curr = record.next;
+        }
+
+        let record = Box::into_raw(Box::new(Record {
+            ctr: AtomicU64::new(0),
+            active: AtomicBool::new(true),
+            next: ptr::null(),
+        }));
+        let mut head = self.records.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*record).next = head };
+            match self
+                .records
+                .compare_exchange(head, record, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return unsafe { &*record },
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// The lowest epoch announced by an online thread: everything retired at or below it
+    /// has had its grace period.
+    fn safe_epoch(&self) -> u64 {
+        // Pairs with the fence in `Registration::online`: either a thread coming online is
+        // counted below, or its first reads already miss what was unlinked before our bump.
+        fence(Ordering::SeqCst);
+        let mut safe = self.epoch.load(Ordering::Acquire);
+        let mut curr = self.records.load(Ordering::Acquire).cast_const();
+        while let Some(record) = unsafe { curr.as_ref() } {
+            let ctr = record.ctr.load(Ordering::Acquire);
+            if ctr != 0 {
+                safe = safe.min(ctr);
+            }
+            curr = record.next;
+        }
+        safe
+    }
+
+    /// Frees whatever orphans have had their grace period, unless another thread is at it.
+    fn reclaim_orphans(&self, safe: u64) {
+        let Ok(mut orphans) = self.orphans.try_lock() else {
+            return;
+        };
+        let expired: Vec<_> = orphans.extract_if(.., |r| r.epoch <= safe).collect();
+        drop(orphans);
+        expired.into_iter().for_each(|r| unsafe { r.free() });
+    }
+}
+
+impl Drop for Domain {
+    fn drop(&mut self) {
+        // Every `Registration` borrows the domain, so no thread can still be reading.
+        let orphans = mem::take(&mut *self.orphans.lock().unwrap());
+        orphans.into_iter().for_each(|r| unsafe { r.free() });
+        let mut record = self.records.load(Ordering::Relaxed);
+        while !record.is_null() {
+            let r = unsafe { Box::from_raw(record) };
+            record = r.next.cast_mut();
+        }
+    }
+}
+
+/// A thread's membership in a [`Domain`], returned by [`Domain::register`]. Dropping it
+/// takes the thread offline for good.
+pub struct Registration<'d> {
+    domain: &'d Domain,
+    record: &'d Record,
+    /// Pointers this thread retired, oldest first.
+    limbo: RefCell<Vec<Retired>>,
+    /// Live [`QsbrGuard`]s, which a quiescent point must not outlive.
+    guards: Cell<usize>,
+    /// The record's counter is only ever written by the registered thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Registration<'_> {
+    /// Announces that this thread holds no reference into any structure of the domain, and
+    /// frees what its own retires have waited long enough for.
+    ///
+    /// # Panics
+    ///
+    /// If a guard pinned through this registration is still alive.
+    pub fn quiescent(&self) {
+        assert_eq!(self.guards.get(), 0, "quiescent point inside a guard");
+        let epoch = self.domain.epoch.load(Ordering::Acquire);
+        // Release: every read this thread made so far is over before anyone frees on it.
+        self.record.ctr.store(epoch, Ordering::Release);
+        if !self.limbo.borrow().is_empty() {
+            self.reclaim();
+        }
+    }
+
+    /// Stops taking part in grace periods, so a thread about to block does not hold up
+    /// reclamation. It must not read any structure of the domain until [`online`] again.
+    ///
+    /// [`online`]: Registration::online
+    ///
+    /// # Panics
+    ///
+    /// If a guard pinned through this registration is still alive.
+    pub fn offline(&self) {
+        assert_eq!(self.guards.get(), 0, "going offline inside a guard");
+        self.record.ctr.store(0, Ordering::Release);
+    }
+
+    /// Resumes taking part in grace periods after [`offline`](Registration::offline).
+    pub fn online(&self) {
+        let epoch = self.domain.epoch.load(Ordering::Relaxed);
+        self.record.ctr.store(epoch, Ordering::Relaxed);
+        // Pairs with the fence in `Domain::safe_epoch`; only paid when coming online, never
+        // by the reads in between.
+        fence(Ordering::SeqCst);
+    }
+
+    /// Hands `ptr` to the domain, which drops it as a `Box<T>` once every online thread has
+    /// passed a quiescent point.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `Box::into_raw`, must already be unreachable for threads that
+    /// have not read it yet, and must not be retired twice. The box may be dropped on
+    /// another thread if this registration is dropped first.
+    pub unsafe fn retire<T>(&self, ptr: *mut T) {
+        // The increment orders the unlink before any announcement of the new epoch.
+        let epoch = self.domain.epoch.fetch_add(1, Ordering::AcqRel) + 1;
+        let len = {
+            let mut limbo = self.limbo.borrow_mut();
+            limbo.push(Retired {
+                epoch,
+                ptr: ptr.cast(),
+                drop: drop_box::<T>,
+            });
+            limbo.len()
+        };
+        if len >= THRESHOLD {
+            self.reclaim();
+        }
+    }
+
+    /// Frees every retired pointer whose grace period has ended, returning how many were
+    /// freed from this thread's own retires.
+    pub fn reclaim(&self) -> usize {
+        let safe = self.domain.safe_epoch();
+        let expired: Vec<_> = {
+            let mut limbo = self.limbo.borrow_mut();
+            let n = limbo.partition_point(|r| r.epoch <= safe);
+            limbo.drain(..n).collect()
+        };
+        let freed = expired.len();
+        // Dropped outside the borrow, since a destructor may retire in turn.
+        expired.into_iter().for_each(|r| unsafe { r.free() });
+        self.domain.reclaim_orphans(safe);
+        freed
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.record.ctr.store(0, Ordering::Release);
+        self.reclaim();
+        let left = mem::take(self.limbo.get_mut());
+        if !left.is_empty() {
+            self.domain.orphans.lock().unwrap().extend(left);
+        }
+        self.record.active.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(not(loom))]
+thread_local! {
+    static LOCAL: RefCell<Option<Registration<'static>>> = const { RefCell::new(None) };
+}
+
+/// Registers the calling thread with the [global](Domain::global) domain, which it needs
+/// before touching a structure built over [`Qsbr`]. Does nothing if it already is.
+#[cfg(not(loom))]
+pub fn register() {
+    LOCAL.with(|l| {
+        l.borrow_mut()
+            .get_or_insert_with(|| Domain::global().register());
+    });
+}
+
+/// Unregisters the calling thread from the global domain; its pending retires are freed by
+/// other threads once their grace period ends. Thread exit does the same.
+///
+/// # Panics
+///
+/// If the thread holds a [`QsbrGuard`].
+#[cfg(not(loom))]
+pub fn unregister() {
+    let registration = LOCAL.with(|l| l.borrow_mut().take());
+    if let Some(r) = &registration {
+        assert_eq!(r.guards.get(), 0, "unregistering inside a guard");
+    }
+    drop(registration);
+}
+
+/// [`Registration::quiescent`] for the calling thread's global registration.
+///
+/// # Panics
+///
+/// If the thread is not registered, or holds a [`QsbrGuard`].
+#[cfg(not(loom))]
+pub fn quiescent() {
+    with_local(Registration::quiescent);
+}
+
+/// [`Registration::offline`] for the calling thread's global registration.
+#[cfg(not(loom))]
+pub fn offline() {
+    with_local(Registration::offline);
+}
+
+/// [`Registration::online`] for the calling thread's global registration.
+#[cfg(not(loom))]
+pub fn online() {
+    with_local(Registration::online);
+}
+
+#[cfg(not(loom))]
+fn with_local<R>(f: impl FnOnce(&Registration<'static>) -> R) -> R {
+    LOCAL.with(|l| {
+        f(l.borrow()
+            .as_ref()
+            .expect("thread not registered with the QSBR domain"))
+    })
+}
+
+/// The [global](Domain::global) QSBR domain as a [`Reclaimer`].
+///
+/// Pinning only checks that the thread is [registered](register) and counts the guard, and
+/// protecting is a plain load. What a guard reads stays valid until the thread's next
+/// [`quiescent`] point, which may not come while the guard is alive.
+#[cfg(not(loom))]
+pub struct Qsbr;
+
+/// Guard of the [`Qsbr`] reclaimer; [`quiescent`] panics while one is alive.
+#[cfg(not(loom))]
+pub struct QsbrGuard {
+    _not_send: PhantomData<*const ()>,
+}
+
+#[cfg(not(loom))]
+impl Drop for QsbrGuard {
+    fn drop(&mut self) {
+        let _ = LOCAL.try_with(|l| {
+            if let Some(r) = &*l.borrow() {
+                r.guards.set(r.guards.get() - 1);
+            }
+        });
+    }
+}
+
+#[cfg(not(loom))]
+unsafe impl Reclaimer for Qsbr {
+    type Guard = QsbrGuard;
+
+    fn pin() -> QsbrGuard {
+        with_local(|r| r.guards.set(r.guards.get() + 1));
+        QsbrGuard {
+            _not_send: PhantomData,
+        }
+    }
+
+    fn protect<T>(_guard: &QsbrGuard, _slot: usize, src: &AtomicPtr<T>) -> *mut T {
+        src.load(Ordering::Acquire)
+    }
+
+    unsafe fn retire<T>(_guard: &QsbrGuard, ptr: *mut T) {
+        with_local(|r| unsafe { r.retire(ptr) });
+    }
+}
+
+#[cfg(not(loom))]
+unsafe impl Optimistic for Qsbr {}
@@ -0,0 +1,11 @@
+/// A raw mutual-exclusion lock that hands out a token on `lock` and takes it back on `unlock`.
+///
+/// The token carries whatever per-acquisition state the algorithm needs (a ticket number, a
+/// queue node), so callers must pass the token they got from `lock` to the matching `unlock`.
+pub trait RawLock: Default + Send + Sync {
+    type Token;
+
+    fn lock(&self) -> Self::Token;
+
+    fn unlock(&self, token: Self::Token);
+}
@@ -0,0 +1,113 @@
+//! Memory reclamation schemes the lock-free structures can be built over.
+//!
+//! [`Stack`](crate::treiberstack::Stack), [`Queue`](crate::msqueue::Queue) and
+//! [`List`](crate::lockfreelist::List) link their nodes through plain `AtomicPtr`s and go
+//! through a [`Reclaimer`] for everything that depends on how unlinked nodes are freed: they
+//! [`pin`](Reclaimer::pin) a guard per operation, [`protect`](Reclaimer::protect) each
+//! pointer before dereferencing it, and [`retire`](Reclaimer::retire) the nodes they unlink.
+//!
+//! Protections live in numbered slots of the guard, and a new protection in a slot ends the
+//! one before it. Traversals therefore step hand over hand, keeping only the few nodes they
+//! still need (a list's `prev`, `curr` and `next`) in slots of their own and reusing the slot
+//! of the node they just left.
+//! [`Epoch`] is the default; [`Hazard`](crate::hazard::Hazard), [`Qsbr`](crate::qsbr::Qsbr)
+//! and [`Leak`] plug in the same way.
+
+use crossbeam_epoch::Shared;
+
+use crate::sync::atomic::{AtomicPtr, Ordering};
+
+/// A memory reclamation scheme.
+///
+/// # Safety
+///
+/// A pointer returned by `protect` must stay dereferenceable until its guard protects
+/// something else in the same slot, is dropped or is passed to `repin`, provided the node it
+/// names had not been retired when `protect` read it. A retired node must not be freed while
+/// any guard still protects it.
+pub unsafe trait Reclaimer: 'static {
+    /// Held for the duration of an operation. What it protects in a slot stays protected
+    /// until that slot is reused, or the guard is dropped or repinned.
+    type Guard;
+
+    fn pin() -> Self::Guard;
+
+    /// Drops every protection of `guard`, letting reclamation move past it; loops that retry
+    /// an operation call it between attempts.
+    fn repin(guard: &mut Self::Guard) {
+        let _ = guard;
+    }
+
+    /// Loads `src` with `Acquire` and protects the node it points to in `slot`, in place of
+    /// whatever that slot protected before. Links may carry a tag in their alignment bits; it
+    /// is returned as loaded, and only the address is protected.
+    ///
+    /// A slot may cost a resource for as long as the guard lives, such as a hazard pointer,
+    /// so callers use small, fixed slot numbers rather than one per node visited.
+    fn protect<T>(guard: &Self::Guard, slot: usize, src: &AtomicPtr<T>) -> *mut T;
+
+    /// Frees `ptr` as a `Box<T>` once no guard protects it any more.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must come from `Box::into_raw`, must already be unlinked so that no new guard can
+    /// protect it, and must not be retired twice.
+    unsafe fn retire<T>(guard: &Self::Guard, ptr: *mut T);
+}
+
+/// Reclaimers under which a guard keeps alive everything that was reachable while it was
+/// held, not just the pointers it protected.
+///
+/// Traversals that follow links out of nodes that may already be unlinked, like the Harris
+/// list's walk over marked nodes or the snapshot iterators, need this; hazard pointers only
+/// support the ones that re-validate every link they follow.
+///
+/// # Safety
+///
+/// A node reachable at any point while a guard is held must not be freed before that guard
+/// is dropped or repinned.
+pub unsafe trait Optimistic: Reclaimer {}
+
+/// `crossbeam_epoch`: a guard pins the current epoch, so protecting is a plain load, and a
+/// retired node is freed once every thread has moved past the epoch it was retired in.
+pub struct Epoch;
+
+unsafe impl Reclaimer for Epoch {
+    type Guard = crossbeam_epoch::Guard;
+
+    fn pin() -> Self::Guard {
+        crossbeam_epoch::pin()
+    }
+
+    fn repin(guard: &mut Self::Guard) {
+        guard.repin();
+    }
+
+    fn protect<T>(_guard: &Self::Guard, _slot: usize, src: &AtomicPtr<T>) -> *mut T {
+        src.load(Ordering::Acquire)
+    }
+
+    unsafe fn retire<T>(guard: &Self::Guard, ptr: *mut T) {
+        unsafe { guard.defer_destroy(Shared::from(ptr.cast_const())) };
+    }
+}
+
+unsafe impl Optimistic for Epoch {}
+
+/// Never frees a retired node. Only for benchmarks, to measure a structure without any
+/// reclamation cost; every unlinked node stays allocated for good.
+pub struct Leak;
+
+unsafe impl Reclaimer for Leak {
+    type Guard = ();
+
+    fn pin() {}
+
+    fn protect<T>(_guard: &(), _slot: usize, src: &AtomicPtr<T>) -> *mut T {
+        src.load(Ordering::Acquire)
+    }
+
+    unsafe fn retire<T>(_guard: &(), _ptr: *mut T) {}
+}
+
+unsafe impl Optimistic for Leak {}
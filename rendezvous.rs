@@ -0,0 +1,450 @@
+use std::{
+    cell::UnsafeCell,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossbeam_epoch::{Atomic, Guard, Owned, Shared, pin, unprotected};
+use crossbeam_utils::CachePadded;
+
+use crate::sync::{
+    atomic::{AtomicU8, Ordering},
+    park_timeout,
+    thread::{self, Thread},
+};
+
+const WAITING: u8 = 0;
+/// A counterpart won the CAS and is exchanging the item.
+const CLAIMED: u8 = 1;
+const DONE: u8 = 2;
+/// The waiter gave up before anyone claimed it.
+const CANCELLED: u8 = 3;
+
+/// Where one waiting `put` or `take` meets its counterpart. The waiter keeps its own `Arc`,
+/// so it can park without holding an epoch guard on the node that published it.
+struct Slot<T> {
+    state: AtomicU8,
+    /// The offered item for a waiting `put`; filled in by the matching `put` for a waiting
+    /// `take`. Only touched by whoever owns the slot: its waiter before publication or after
+    /// cancelling, the claimer between `CLAIMED` and `DONE`, the waiter again after `DONE`.
+    item: UnsafeCell<Option<T>>,
+    waiter: Thread,
+}
+
+unsafe impl<T: Send> Sync for Slot<T> {}
+unsafe impl<T: Send> Send for Slot<T> {}
+
+impl<T> Slot<T> {
+    fn new(item: Option<T>) -> Arc<Self> {
+        Arc::new(Self {
+            state: AtomicU8::new(WAITING),
+            item: UnsafeCell::new(item),
+            waiter: thread::current(),
+        })
+    }
+
+    fn is_waiting(&self) -> bool {
+        self.state.load(Ordering::Acquire) == WAITING
+    }
+
+    /// Hands `item` (`None` from a take) to the waiter and returns what it offered (`None`
+    /// from a take), or gives `item` back if the waiter was already matched or gone.
+    fn fulfill(&self, item: Option<T>) -> Result<Option<T>, Option<T>> {
+        if self
+            .state
+            .compare_exchange(WAITING, CLAIMED, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(item);
+        }
+        let offered = unsafe { (*self.item.get()).take() };
+        unsafe { *self.item.get() = item };
+        self.state.store(DONE, Ordering::Release);
+        self.waiter.unpark();
+        Ok(offered)
+    }
+
+    /// Parks until matched, returning what the counterpart left, or until `deadline`,
+    /// returning the item the waiter offered.
+    fn wait(&self, deadline: Option<Instant>) -> Result<Option<T>, Option<T>> {
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                DONE => return Ok(unsafe { (*self.item.get()).take() }),
+                // Matched: the exchange finishes without blocking, so just wait it out.
+                CLAIMED => thread::yield_now(),
+                _ => match deadline.map(|d| d.checked_duration_since(Instant::now())) {
+                    None => thread::park(),
+                    Some(Some(r)) if !r.is_zero() => park_timeout(r),
+                    Some(_) => {
+                        if self
+                            .state
+                            .compare_exchange(
+                                WAITING,
+                                CANCELLED,
+                                Ordering::AcqRel,
+                                Ordering::Acquire,
+                            )
+                            .is_ok()
+                        {
+                            return Err(unsafe { (*self.item.get()).take() });
+                        }
+                    }
+                },
+            }
+        }
+    }
+
+    /// Takes the item back from a slot that was never published.
+    fn into_item(self: Arc<Self>) -> Option<T> {
+        unsafe { (*self.item.get()).take() }
+    }
+}
+
+/// A waiting `put` (data) or `take` (reservation) linked into a dual structure.
+struct Node<T> {
+    is_data: bool,
+    /// `None` only for the dual queue's initial sentinel.
+    slot: Option<Arc<Slot<T>>>,
+    next: Atomic<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn slot(&self) -> &Slot<T> {
+        self.slot.as_deref().expect("only the sentinel has no slot")
+    }
+}
+
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|d| d <= Instant::now())
+}
+
+/// One rendezvous: `item` is `Some` for a put, `None` for a take. Returns what the
+/// counterpart handed over (`None` to a put), or gives `item` back once `deadline` passes
+/// without a match.
+trait Transfer<T> {
+    fn transfer(&self, item: Option<T>, deadline: Option<Instant>) -> Result<Option<T>, Option<T>>;
+}
+
+/// Scherer–Lea–Scott nonblocking dual queue: a Michael–Scott queue whose nodes are either all
+/// waiting puts or all waiting takes. An operation of the opposite kind fulfills the node
+/// after the sentinel, so waiters are matched in arrival order.
+struct DualQueue<T> {
+    head: CachePadded<Atomic<Node<T>>>,
+    tail: CachePadded<Atomic<Node<T>>>,
+}
+
+impl<T> DualQueue<T> {
+    fn new() -> Self {
+        let sentinel = Owned::new(Node {
+            is_data: false,
+            slot: None,
+            next: Atomic::null(),
+        })
+        .into_shared(unsafe { unprotected() });
+        Self {
+            head: CachePadded::new(Atomic::from(sentinel)),
+            tail: CachePadded::new(Atomic::from(sentinel)),
+        }
+    }
+
+    /// Moves `head` from `h` to `next`, retiring `h`.
+    fn advance_head<'g>(
+        &self,
+        h: Shared<'g, Node<T>>,
+        next: Shared<'g, Node<T>>,
+        guard: &'g Guard,
+    ) {
+        if self
+            .head
+            .compare_exchange(h, next, Ordering::Release, Ordering::Relaxed, guard)
+            .is_ok()
+        {
+            unsafe { guard.defer_destroy(h) };
+        }
+    }
+}
+
+impl<T> Transfer<T> for DualQueue<T> {
+    fn transfer(&self, item: Option<T>, deadline: Option<Instant>) -> Result<Option<T>, Option<T>> {
+        let is_data = item.is_some();
+        let mut item = item;
+        loop {
+            let pinned = pin();
+            let guard = &pinned;
+            let h = self.head.load(Ordering::Acquire, guard);
+            let t = self.tail.load(Ordering::Acquire, guard);
+            let t_ref = unsafe { t.deref() };
+
+            if h == t || t_ref.is_data == is_data {
+                // Empty or holding waiters like us: wait at the tail.
+                let next = t_ref.next.load(Ordering::Acquire, guard);
+                if !next.is_null() {
+                    let _ = self.tail.compare_exchange(
+                        t,
+                        next,
+                        Ordering::Release,
+                        Ordering::Relaxed,
+                        guard,
+                    );
+                    continue;
+                }
+                if expired(deadline) {
+                    return Err(item);
+                }
+                let slot = Slot::new(item.take());
+                let node = Owned::new(Node {
+                    is_data,
+                    slot: Some(Arc::clone(&slot)),
+                    next: Atomic::null(),
+                });
+                match t_ref.next.compare_exchange(
+                    Shared::null(),
+                    node,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                ) {
+                    Ok(node) => {
+                        let _ = self.tail.compare_exchange(
+                            t,
+                            node,
+                            Ordering::Release,
+                            Ordering::Relaxed,
+                            guard,
+                        );
+                    }
+                    Err(_) => {
+                        item = slot.into_item();
+                        continue;
+                    }
+                }
+                // Never park pinned: that would stall reclamation for every other thread.
+                drop(pinned);
+                return slot.wait(deadline);
+            }
+
+            // Holding complementary waiters: fulfill the oldest, skipping any that are gone.
+            let m = unsafe { h.deref() }.next.load(Ordering::Acquire, guard);
+            let Some(m_ref) = (unsafe { m.as_ref() }) else {
+                continue;
+            };
+            if self.head.load(Ordering::Acquire, guard) != h {
+                continue;
+            }
+            let result = m_ref.slot().fulfill(item.take());
+            self.advance_head(h, m, guard);
+            match result {
+                Ok(offered) => return Ok(offered),
+                Err(back) => item = back,
+            }
+        }
+    }
+}
+
+impl<T> Drop for DualQueue<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut node = self.head.load(Ordering::Relaxed, guard);
+            while !node.is_null() {
+                let next = node.deref().next.load(Ordering::Relaxed, guard);
+                drop(node.into_owned());
+                node = next;
+            }
+        }
+    }
+}
+
+/// Scherer–Lea–Scott nonblocking dual stack: a Treiber stack whose live nodes are either all
+/// waiting puts or all waiting takes. An operation of the opposite kind fulfills the top, so
+/// the most recent waiter is matched first.
+///
+/// Matching claims the top's slot and then pops it; a node claimed or cancelled while others
+/// were pushed above it stays buried until it resurfaces and is popped as dead.
+struct DualStack<T> {
+    head: CachePadded<Atomic<Node<T>>>,
+}
+
+impl<T> DualStack<T> {
+    fn new() -> Self {
+        Self {
+            head: CachePadded::new(Atomic::null()),
+        }
+    }
+
+    fn pop<'g>(&self, top: Shared<'g, Node<T>>, guard: &'g Guard) {
+        let next = unsafe { top.deref() }.next.load(Ordering::Acquire, guard);
+        if self
+            .head
+            .compare_exchange(top, next, Ordering::Release, Ordering::Relaxed, guard)
+            .is_ok()
+        {
+            unsafe { guard.defer_destroy(top) };
+        }
+    }
+}
+
+impl<T> Transfer<T> for DualStack<T> {
+    fn transfer(&self, item: Option<T>, deadline: Option<Instant>) -> Result<Option<T>, Option<T>> {
+        let is_data = item.is_some();
+        let mut item = item;
+        loop {
+            let pinned = pin();
+            let guard = &pinned;
+            let top = self.head.load(Ordering::Acquire, guard);
+            let top_ref = unsafe { top.as_ref() };
+
+            match top_ref {
+                Some(n) if !n.slot().is_waiting() => {
+                    // A dead node surfaced: clear it away first.
+                    self.pop(top, guard);
+                }
+                Some(n) if n.is_data != is_data => {
+                    let result = n.slot().fulfill(item.take());
+                    self.pop(top, guard);
+                    match result {
+                        Ok(offered) => return Ok(offered),
+                        Err(back) => item = back,
+                    }
+                }
+                _ => {
+                    // Empty or holding waiters like us: wait on top.
+                    if expired(deadline) {
+                        return Err(item);
+                    }
+                    let slot = Slot::new(item.take());
+                    let node = Owned::new(Node {
+                        is_data,
+                        slot: Some(Arc::clone(&slot)),
+                        next: Atomic::from(top),
+                    });
+                    if self
+                        .head
+                        .compare_exchange(top, node, Ordering::Release, Ordering::Relaxed, guard)
+                        .is_err()
+                    {
+                        item = slot.into_item();
+                        continue;
+                    }
+                    drop(pinned);
+                    return slot.wait(deadline);
+                }
+            }
+        }
+    }
+}
+
+impl<T> Drop for DualStack<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut node = self.head.load(Ordering::Relaxed, guard);
+            while !node.is_null() {
+                let next = node.deref().next.load(Ordering::Relaxed, guard);
+                drop(node.into_owned());
+                node = next;
+            }
+        }
+    }
+}
+
+enum Mode<T> {
+    Fair(DualQueue<T>),
+    Unfair(DualStack<T>),
+}
+
+/// A zero-capacity handoff: every `put` waits for a `take` to receive its item, and every
+/// `take` waits for a `put`.
+///
+/// In fair mode waiters are matched first come, first served, through a nonblocking dual
+/// queue; unfair mode uses a dual stack instead, matching the most recent waiter, which tends
+/// to keep hot threads running and cold ones parked. Waiters park, and the timed variants
+/// withdraw cleanly when their deadline passes.
+pub struct SynchronousQueue<T> {
+    mode: Mode<T>,
+}
+
+unsafe impl<T: Send> Sync for SynchronousQueue<T> {}
+unsafe impl<T: Send> Send for SynchronousQueue<T> {}
+
+impl<T> SynchronousQueue<T> {
+    /// Matches waiters in arrival order.
+    pub fn fair() -> Self {
+        Self {
+            mode: Mode::Fair(DualQueue::new()),
+        }
+    }
+
+    /// Matches the most recent waiter first.
+    pub fn unfair() -> Self {
+        Self {
+            mode: Mode::Unfair(DualStack::new()),
+        }
+    }
+
+    fn transfer(&self, item: Option<T>, deadline: Option<Instant>) -> Result<Option<T>, Option<T>> {
+        match &self.mode {
+            Mode::Fair(q) => q.transfer(item, deadline),
+            Mode::Unfair(s) => s.transfer(item, deadline),
+        }
+    }
+
+    /// Blocks until a `take` receives `t`.
+    pub fn put(&self, t: T) {
+        let _ = self.transfer(Some(t), None);
+    }
+
+    /// Blocks until a `put` hands over an item.
+    pub fn take(&self) -> T {
+        self.transfer(None, None)
+            .ok()
+            .flatten()
+            .expect("an untimed take only returns with an item")
+    }
+
+    /// Waits at most `timeout` for a `take`; gives `t` back if none came.
+    pub fn offer(&self, t: T, timeout: Duration) -> Result<(), T> {
+        match self.transfer(Some(t), Some(Instant::now() + timeout)) {
+            Ok(_) => Ok(()),
+            Err(t) => Err(t.expect("a put offers an item")),
+        }
+    }
+
+    /// Waits at most `timeout` for a `put`.
+    pub fn poll(&self, timeout: Duration) -> Option<T> {
+        self.transfer(None, Some(Instant::now() + timeout))
+            .ok()
+            .flatten()
+    }
+
+    /// Hands `t` to a `take` that is already waiting, or gives it back.
+    pub fn try_put(&self, t: T) -> Result<(), T> {
+        self.offer(t, Duration::ZERO)
+    }
+
+    /// Receives from a `put` that is already waiting.
+    pub fn try_take(&self) -> Option<T> {
+        self.poll(Duration::ZERO)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn timeouts_return_the_item() {
+        for queue in [SynchronousQueue::fair(), SynchronousQueue::unfair()] {
+            let counts = DropCounts::new(2);
+            assert_eq!(queue.try_put(counts.track(1)).unwrap_err().value, 1);
+            let back = queue.offer(counts.track(2), Duration::from_millis(5));
+            assert_eq!(back.unwrap_err().value, 2);
+            assert!(queue.poll(Duration::from_millis(5)).is_none());
+            // Cancelled nodes may still be linked; dropping the queue must not drop the
+            // items again.
+            drop(queue);
+            counts.assert_all_dropped();
+        }
+    }
+}
@@ -0,0 +1,419 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    channel::{Receiver, RecvError, TryRecvError},
+    msqueue::Queue,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        park_timeout,
+        thread::{self, Thread},
+    },
+    util::random,
+};
+
+/// One-shot wakeup a blocked [`Select`] registers with every queue it waits on.
+pub struct Signal {
+    fired: AtomicBool,
+    thread: Thread,
+}
+
+impl Signal {
+    fn new() -> Self {
+        Self {
+            fired: AtomicBool::new(false),
+            thread: thread::current(),
+        }
+    }
+
+    pub(crate) fn fire(&self) {
+        self.fired.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+
+    /// Parks until fired or `deadline` passes; returns whether it fired.
+    fn wait(&self, deadline: Option<Instant>) -> bool {
+        while !self.fired.load(Ordering::Acquire) {
+            match deadline {
+                None => thread::park(),
+                Some(d) => match d.checked_duration_since(Instant::now()) {
+                    Some(r) if !r.is_zero() => park_timeout(r),
+                    _ => return false,
+                },
+            }
+        }
+        true
+    }
+}
+
+/// Something a [`Select`] can wait on.
+pub trait Selectable {
+    /// Whether a receive would complete (with a value or a disconnection) without blocking.
+    fn is_ready(&self) -> bool;
+
+    /// Fires `signal` on the next push or disconnection until `unwatch` is called.
+    fn watch(&self, signal: &Arc<Signal>);
+
+    fn unwatch(&self, signal: &Arc<Signal>);
+}
+
+/// The receive half of a [`Selectable`], used by [`select!`](crate::select!) to complete the
+/// operation that was reported ready.
+pub trait SelectRecv: Selectable {
+    type Output;
+
+    /// Attempts the receive; `None` means another consumer took the value first.
+    fn try_select(&self) -> Option<Self::Output>;
+}
+
+impl<T> Selectable for Queue<T> {
+    fn is_ready(&self) -> bool {
+        !self.is_empty()
+    }
+
+    fn watch(&self, signal: &Arc<Signal>) {
+        Queue::watch(self, signal);
+    }
+
+    fn unwatch(&self, signal: &Arc<Signal>) {
+        Queue::unwatch(self, signal);
+    }
+}
+
+impl<T> SelectRecv for Queue<T> {
+    type Output = T;
+
+    fn try_select(&self) -> Option<T> {
+        self.pop()
+    }
+}
+
+impl<T> Selectable for Receiver<T> {
+    fn is_ready(&self) -> bool {
+        !self.queue().is_empty() || self.is_disconnected()
+    }
+
+    fn watch(&self, signal: &Arc<Signal>) {
+        self.queue().watch(signal);
+    }
+
+    fn unwatch(&self, signal: &Arc<Signal>) {
+        self.queue().unwatch(signal);
+    }
+}
+
+impl<T> SelectRecv for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn try_select(&self) -> Option<Result<T, RecvError>> {
+        match self.try_recv() {
+            Ok(t) => Some(Ok(t)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+        }
+    }
+}
+
+impl<S: Selectable + ?Sized> Selectable for &S {
+    fn is_ready(&self) -> bool {
+        (**self).is_ready()
+    }
+
+    fn watch(&self, signal: &Arc<Signal>) {
+        (**self).watch(signal)
+    }
+
+    fn unwatch(&self, signal: &Arc<Signal>) {
+        (**self).unwatch(signal)
+    }
+}
+
+impl<S: SelectRecv + ?Sized> SelectRecv for &S {
+    type Output = S::Output;
+
+    fn try_select(&self) -> Option<S::Output> {
+        (**self).try_select()
+    }
+}
+
+/// Waits on several queues or channels at once.
+///
+/// Register operations with [`Select::recv`], then wait with [`Select::ready`] (or its timed and
+/// non-blocking variants), which returns the index of an operation that was ready. Readiness
+/// is a hint: with other consumers around, the receive can still come back empty and the
+/// caller should select again, which is what [`select!`](crate::select!) does. When several
+/// operations are ready the winner is chosen at random, so no source can starve the others.
+#[derive(Default)]
+pub struct Select<'a> {
+    handles: Vec<&'a dyn Selectable>,
+}
+
+impl<'a> Select<'a> {
+    pub fn new() -> Self {
+        Self {
+            handles: Vec::new(),
+        }
+    }
+
+    /// Adds a receive operation and returns its index.
+    pub fn recv<S: Selectable>(&mut self, source: &'a S) -> usize {
+        self.handles.push(source);
+        self.handles.len() - 1
+    }
+
+    /// Returns a ready operation without blocking, if there is one.
+    pub fn try_ready(&self) -> Option<usize> {
+        let n = self.handles.len();
+        if n == 0 {
+            return None;
+        }
+        let start = random(n);
+        (0..n)
+            .map(|i| (start + i) % n)
+            .find(|&i| self.handles[i].is_ready())
+    }
+
+    /// Blocks until one of the operations is ready.
+    pub fn ready(&self) -> usize {
+        self.ready_until(None)
+            .expect("select without a deadline only returns with a ready operation")
+    }
+
+    /// Blocks for at most `timeout` until one of the operations is ready.
+    pub fn ready_timeout(&self, timeout: Duration) -> Option<usize> {
+        self.ready_until(Some(Instant::now() + timeout))
+    }
+
+    fn ready_until(&self, deadline: Option<Instant>) -> Option<usize> {
+        assert!(
+            !self.handles.is_empty() || deadline.is_some(),
+            "blocking select with no operations would never return"
+        );
+        loop {
+            if let Some(i) = self.try_ready() {
+                return Some(i);
+            }
+            let signal = Arc::new(Signal::new());
+            for h in &self.handles {
+                h.watch(&signal);
+            }
+            // Watching before the re-check means a push we miss here will fire the signal.
+            let ready = self.try_ready();
+            let fired = ready.is_none() && signal.wait(deadline);
+            for h in &self.handles {
+                h.unwatch(&signal);
+            }
+            if ready.is_some() {
+                return ready;
+            }
+            if !fired {
+                return self.try_ready();
+            }
+        }
+    }
+}
+
+/// Waits on several receive operations and runs the arm of the one that completes.
+///
+/// ```ignore
+/// select! {
+///     recv(control) -> msg => handle_control(msg),
+///     recv(data) -> msg => handle_data(msg),
+///     default(Duration::from_millis(10)) => idle(),
+/// }
+/// ```
+///
+/// Each `recv` source is a [`Queue`] (yielding `T`) or a [`Receiver`] (yielding
+/// `Result<T, RecvError>`), and is evaluated once. A trailing `default => ..` arm runs when
+/// nothing is ready, `default(timeout) => ..` when nothing becomes ready in time; without
+/// either, `select!` blocks. Ready arms are picked at random.
+#[macro_export]
+macro_rules! select {
+    (@parse [$($arms:tt)*] recv($source:expr) -> $pat:pat => $body:expr $(, $($rest:tt)*)?) => {
+        $crate::select!(@parse [$($arms)* ($source, $pat, $body)] $($($rest)*)?)
+    };
+    (@parse [$($arms:tt)*] default => $body:expr $(,)?) => {
+        $crate::select!(@bind [] [$($arms)*] (try_ready()) $body)
+    };
+    (@parse [$($arms:tt)*] default($timeout:expr) => $body:expr $(,)?) => {
+        $crate::select!(@bind [] [$($arms)*] (ready_timeout($timeout)) $body)
+    };
+    (@parse [$($arms:tt)*]) => {
+        $crate::select!(
+            @bind [] [$($arms)*] (ready_until_none())
+            unreachable!("a blocking select always completes an operation")
+        )
+    };
+    // Bind each source and its result slot once; every level of recursion gets fresh hygienic
+    // names, which are carried along as tokens to the final expansion.
+    (@bind [$($bound:tt)*] [($source:expr, $pat:pat, $body:expr) $($arms:tt)*] $wait:tt $default:expr) => {{
+        let source = &$source;
+        let mut slot = ::core::option::Option::None;
+        $crate::select!(@bind [$($bound)* (source, slot, $pat, $body)] [$($arms)*] $wait $default)
+    }};
+    (@bind [$(($source:ident, $slot:ident, $pat:pat, $body:expr))*] [] ($($wait:tt)*) $default:expr) => {{
+        let mut sel = $crate::select::Select::new();
+        $( sel.recv($source); )*
+        loop {
+            let Some(ready) = $crate::select!(@wait sel $($wait)*) else {
+                break;
+            };
+            let mut index = 0;
+            $(
+                if ready == index {
+                    $slot = $crate::select::SelectRecv::try_select($source);
+                    if $slot.is_some() {
+                        break;
+                    }
+                    continue;
+                }
+                index += 1;
+            )*
+            let _ = index;
+        }
+        $(
+            if let ::core::option::Option::Some(msg) = $slot {
+                let $pat = msg;
+                $body
+            } else
+        )* {
+            $default
+        }
+    }};
+    (@wait $sel:ident ready_until_none()) => {
+        ::core::option::Option::Some($sel.ready())
+    };
+    (@wait $sel:ident $($wait:tt)*) => {
+        $sel.$($wait)*
+    };
+    ($($tokens:tt)*) => {
+        $crate::select!(@parse [] $($tokens)*)
+    };
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::channel::unbounded;
+
+    #[test]
+    fn try_ready_and_ready_timeout() {
+        let (a, b) = (Queue::<i32>::new(), Queue::new());
+        assert_eq!(Select::new().try_ready(), None);
+        assert_eq!(Select::new().ready_timeout(Duration::ZERO), None);
+
+        let mut sel = Select::new();
+        assert_eq!(sel.recv(&a), 0);
+        assert_eq!(sel.recv(&b), 1);
+        assert_eq!(sel.try_ready(), None);
+        let start = Instant::now();
+        assert_eq!(sel.ready_timeout(Duration::from_millis(30)), None);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        b.push(1);
+        assert_eq!(sel.try_ready(), Some(1));
+        assert_eq!(sel.ready_timeout(Duration::from_secs(60)), Some(1));
+        assert_eq!(sel.ready(), 1);
+    }
+
+    #[test]
+    fn ready_wakes_on_push_and_disconnect() {
+        let queue = Queue::new();
+        let (s, r) = unbounded::<i32>();
+        let mut sel = Select::new();
+        sel.recv(&queue);
+        sel.recv(&r);
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                queue.push(1);
+            });
+            assert_eq!(sel.ready(), 0);
+        });
+        assert_eq!(queue.pop(), Some(1));
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                drop(s);
+            });
+            assert_eq!(sel.ready_timeout(Duration::from_secs(60)), Some(1));
+        });
+    }
+
+    #[test]
+    fn ready_picks_among_ready_operations_at_random() {
+        let (a, b) = (Queue::new(), Queue::new());
+        a.push(());
+        b.push(());
+        let mut sel = Select::new();
+        sel.recv(&a);
+        sel.recv(&b);
+        let mut hits = [0; 2];
+        for _ in 0..1000 {
+            hits[sel.ready()] += 1;
+        }
+        assert!(hits.iter().all(|&h| h > 100), "{hits:?}");
+    }
+
+    #[test]
+    #[should_panic(expected = "blocking select with no operations")]
+    fn blocking_on_nothing_panics() {
+        Select::new().ready();
+    }
+
+    #[test]
+    fn select_macro_arms() {
+        let (a, b) = (Queue::new(), Queue::new());
+        let (s, r) = unbounded();
+
+        // `default` runs when nothing is ready, and the sources stay untouched.
+        let got = crate::select! {
+            recv(a) -> v => Some(v),
+            recv(r) -> msg => Some(msg.unwrap()),
+            default => None,
+        };
+        assert_eq!(got, None);
+
+        b.push(2);
+        let got = crate::select! {
+            recv(a) -> v => v,
+            recv(b) -> v => v * 10,
+            default => 0,
+        };
+        assert_eq!(got, 20);
+
+        let start = Instant::now();
+        let got = crate::select! {
+            recv(a) -> v => v,
+            default(Duration::from_millis(30)) => -1,
+        };
+        assert_eq!(got, -1);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        // Without a default the select blocks until a push arrives.
+        let got = thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                s.send(3).unwrap();
+            });
+            crate::select! {
+                recv(a) -> v => v,
+                recv(r) -> msg => msg.unwrap() + 100,
+            }
+        });
+        assert_eq!(got, 103);
+
+        // A disconnected channel completes its arm with the error.
+        drop(s);
+        let got = crate::select! {
+            recv(a) -> _v => unreachable!(),
+            recv(r) -> msg => msg,
+        };
+        assert_eq!(got, Err(RecvError));
+        assert!(a.is_empty() && b.is_empty());
+    }
+}
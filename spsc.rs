@@ -0,0 +1,211 @@
+use std::{cell::UnsafeCell, mem::MaybeUninit, sync::Arc};
+
+use crossbeam_utils::CachePadded;
+
+use crate::sync::atomic::{AtomicUsize, Ordering};
+
+struct Ring<T> {
+    /// Next position to pop; only the consumer writes it.
+    head: CachePadded<AtomicUsize>,
+    /// Next position to push; only the producer writes it.
+    tail: CachePadded<AtomicUsize>,
+    buffer: Box<[UnsafeCell<MaybeUninit<T>>]>,
+}
+
+unsafe impl<T: Send> Sync for Ring<T> {}
+unsafe impl<T: Send> Send for Ring<T> {}
+
+impl<T> Ring<T> {
+    fn slot(&self, pos: usize) -> *mut MaybeUninit<T> {
+        self.buffer[pos % self.buffer.len()].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let tail = self.tail.load(Ordering::Relaxed);
+        for pos in self.head.load(Ordering::Relaxed)..tail {
+            unsafe { (*self.slot(pos)).assume_init_drop() };
+        }
+    }
+}
+
+/// The pushing half of a [`ring`].
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+    tail: usize,
+    /// Last `head` read from the consumer; free space is only re-read once this runs out.
+    cached_head: usize,
+}
+
+/// The popping half of a [`ring`].
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+    head: usize,
+    /// Last `tail` read from the producer; it is only re-read once this runs dry.
+    cached_tail: usize,
+}
+
+/// Creates a bounded single-producer single-consumer ring holding at most `capacity`
+/// elements.
+///
+/// Each side owns its index and keeps a cached copy of the other's, so the shared cache lines
+/// are only touched when the cached view says the ring is full (or empty), and a batch
+/// publishes with one store. Neither side ever waits on the other: every operation is
+/// wait-free.
+///
+/// # Panics
+///
+/// If `capacity` is zero.
+pub fn ring<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    assert!(capacity > 0, "capacity must be non-zero");
+    let ring = Arc::new(Ring {
+        head: CachePadded::new(AtomicUsize::new(0)),
+        tail: CachePadded::new(AtomicUsize::new(0)),
+        buffer: (0..capacity)
+            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+            .collect(),
+    });
+    (
+        Producer {
+            ring: Arc::clone(&ring),
+            tail: 0,
+            cached_head: 0,
+        },
+        Consumer {
+            ring,
+            head: 0,
+            cached_tail: 0,
+        },
+    )
+}
+
+impl<T> Producer<T> {
+    /// Free slots, re-reading the consumer's index only if fewer than `want` are known free.
+    fn free(&mut self, want: usize) -> usize {
+        let cap = self.ring.buffer.len();
+        if cap - (self.tail - self.cached_head) < want {
+            // Acquire: the consumer has finished reading every slot before `head`.
+            self.cached_head = self.ring.head.load(Ordering::Acquire);
+        }
+        cap - (self.tail - self.cached_head)
+    }
+
+    /// Pushes `t`, or returns it if the ring is full.
+    pub fn push(&mut self, t: T) -> Result<(), T> {
+        if self.free(1) == 0 {
+            return Err(t);
+        }
+        unsafe { (*self.ring.slot(self.tail)).write(t) };
+        self.tail += 1;
+        self.ring.tail.store(self.tail, Ordering::Release);
+        Ok(())
+    }
+
+    /// Pushes as many of `items` as fit, in order, and returns how many that was. The whole
+    /// batch becomes visible to the consumer at once.
+    pub fn push_slice(&mut self, items: &[T]) -> usize
+    where
+        T: Copy,
+    {
+        let n = self.free(items.len()).min(items.len());
+        for (i, &t) in items[..n].iter().enumerate() {
+            unsafe { (*self.ring.slot(self.tail + i)).write(t) };
+        }
+        if n > 0 {
+            self.tail += n;
+            self.ring.tail.store(self.tail, Ordering::Release);
+        }
+        n
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.buffer.len()
+    }
+}
+
+impl<T> Consumer<T> {
+    /// Filled slots, re-reading the producer's index only if fewer than `want` are known
+    /// filled.
+    fn filled(&mut self, want: usize) -> usize {
+        if self.cached_tail - self.head < want {
+            // Acquire: the producer has finished writing every slot before `tail`.
+            self.cached_tail = self.ring.tail.load(Ordering::Acquire);
+        }
+        self.cached_tail - self.head
+    }
+
+    /// Pops the oldest element, or returns `None` if the ring is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.filled(1) == 0 {
+            return None;
+        }
+        let t = unsafe { (*self.ring.slot(self.head)).assume_init_read() };
+        self.head += 1;
+        self.ring.head.store(self.head, Ordering::Release);
+        Some(t)
+    }
+
+    /// Pops up to `out.len()` elements into the front of `out`, oldest first, and returns how
+    /// many that was. The freed slots are handed back to the producer at once.
+    pub fn pop_into(&mut self, out: &mut [T]) -> usize {
+        let n = self.filled(out.len()).min(out.len());
+        for (i, o) in out[..n].iter_mut().enumerate() {
+            *o = unsafe { (*self.ring.slot(self.head + i)).assume_init_read() };
+        }
+        if n > 0 {
+            self.head += n;
+            self.ring.head.store(self.head, Ordering::Release);
+        }
+        n
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.ring.buffer.len()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn slices_wrap_around_the_buffer() {
+        let (mut producer, mut consumer) = ring(5);
+        let mut out = [0; 4];
+        assert_eq!(consumer.pop_into(&mut out), 0);
+        assert_eq!(producer.push_slice(&[0, 1, 2]), 3);
+        assert_eq!(consumer.pop_into(&mut out[..2]), 2);
+        // Slots 3 and 4, then 0 to 2 again: only four of the six fit.
+        assert_eq!(producer.push_slice(&[3, 4, 5, 6, 7, 8]), 4);
+        assert_eq!(producer.push(9), Err(9));
+        assert_eq!(consumer.pop_into(&mut out), 4);
+        assert_eq!(out, [2, 3, 4, 5]);
+        assert_eq!(consumer.pop(), Some(6));
+        assert_eq!(consumer.pop(), None);
+    }
+
+    #[test]
+    fn drop_drops_what_is_left() {
+        for consumer_first in [false, true] {
+            let counts = DropCounts::new(4);
+            let (mut producer, mut consumer) = ring(3);
+            for v in 0..3 {
+                assert!(producer.push(counts.track(v)).is_ok());
+            }
+            drop(consumer.pop());
+            assert!(producer.push(counts.track(3)).is_ok());
+            // The ring goes with whichever half is dropped last.
+            if consumer_first {
+                drop(consumer);
+                assert_eq!(counts.dropped(), 1);
+                drop(producer);
+            } else {
+                drop(producer);
+                drop(consumer);
+            }
+            counts.assert_all_dropped();
+        }
+    }
+}
@@ -0,0 +1,1120 @@
+//! Randomized many-thread runs of the concurrent structures at sizes loom cannot explore,
+//! checking that every element comes out exactly once. Built for plain `cargo test`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    future::Future,
+    pin::pin,
+    ptr,
+    sync::{
+        Arc, Barrier, Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicIsize, AtomicPtr, Ordering},
+    },
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use crate::{
+    arrayqueue::ArrayQueue,
+    asyncqueue::AsyncQueue,
+    broadcast::{self, Mode, RecvError, TryRecvError},
+    clhlock::Clhlock,
+    deque::{Steal, deque},
+    elimination::EliminationStack,
+    exchanger::Exchanger,
+    faaqueue::FaaQueue,
+    hazard::{Domain, Hazard, HazardPointer},
+    lockfreelist::List,
+    mcslock::McsLock,
+    mcsparklock::McsParkLock,
+    msqueue::Queue,
+    qsbr::{self, Qsbr},
+    rawlock::RawLock,
+    reclaim::{Epoch, Leak, Reclaimer},
+    rendezvous::SynchronousQueue,
+    spsc,
+    taggedstack::TaggedStack,
+    testutil::{DropCounts, scan_global_until},
+    ticketlock::TicketLock,
+    treiberstack::Stack,
+    twolockqueue::TwoLockQueue,
+};
+
+const THIEVES: usize = 3;
+
+#[test]
+fn arrayqueue_backpressure_mpmc() {
+    const PRODUCERS: usize = 4;
+    const CONSUMERS: usize = 4;
+    const N: usize = 50_000;
+    // Small enough that producers keep running into a full queue and consumers into an
+    // empty one.
+    let queue = ArrayQueue::new(8);
+    let barrier = Barrier::new(PRODUCERS + CONSUMERS);
+
+    let popped: Vec<Vec<(usize, usize)>> = thread::scope(|s| {
+        for p in 0..PRODUCERS {
+            let (queue, barrier) = (&queue, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for i in 0..N {
+                    let mut item = (p, i);
+                    while let Err(back) = queue.push(item) {
+                        item = back;
+                        thread::yield_now();
+                    }
+                }
+            });
+        }
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let (queue, barrier) = (&queue, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let mut got = Vec::with_capacity(N);
+                    while got.len() < N * PRODUCERS / CONSUMERS {
+                        match queue.pop() {
+                            Some(v) => got.push(v),
+                            None => thread::yield_now(),
+                        }
+                        assert!(queue.len() <= queue.capacity());
+                    }
+                    got
+                })
+            })
+            .collect();
+        consumers.into_iter().map(|c| c.join().unwrap()).collect()
+    });
+
+    for got in &popped {
+        let mut last = [None; PRODUCERS];
+        for &(p, i) in got {
+            assert!(last[p] < Some(i), "producer {p} reordered");
+            last[p] = Some(i);
+        }
+    }
+    let mut all: Vec<_> = popped.into_iter().flatten().collect();
+    all.sort_unstable();
+    let expected: Vec<_> = (0..PRODUCERS)
+        .flat_map(|p| (0..N).map(move |i| (p, i)))
+        .collect();
+    assert_eq!(all, expected);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn spsc_batches_across_wraparound() {
+    const N: u64 = 200_000;
+    // An odd capacity against varying batch sizes makes batches straddle the buffer's end.
+    let (mut producer, mut consumer) = spsc::ring(7);
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            let mut next = 0;
+            let mut batch = Vec::new();
+            while next < N {
+                batch.clear();
+                batch.extend(next..(next + next % 5 + 1).min(N));
+                let pushed = producer.push_slice(&batch) as u64;
+                if pushed == 0 {
+                    thread::yield_now();
+                }
+                next += pushed;
+            }
+        });
+        let mut expected = 0;
+        let mut out = [0; 4];
+        while expected < N {
+            let want = (expected % 4 + 1) as usize;
+            let n = consumer.pop_into(&mut out[..want]);
+            if n == 0 {
+                thread::yield_now();
+            }
+            for &v in &out[..n] {
+                assert_eq!(v, expected, "lost, duplicated or reordered");
+                expected += 1;
+            }
+        }
+        assert_eq!(consumer.pop(), None);
+    });
+}
+
+#[test]
+fn deque_owner_and_thieves() {
+    const N: usize = 100_000;
+    let (worker, stealer) = deque();
+    let done = AtomicBool::new(false);
+    let barrier = Barrier::new(THIEVES + 1);
+
+    let mut seen = thread::scope(|s| {
+        let thieves: Vec<_> = (0..THIEVES)
+            .map(|i| {
+                let (stealer, done, barrier) = (stealer.clone(), &done, &barrier);
+                s.spawn(move || {
+                    let (local, _) = deque();
+                    let mut got = Vec::new();
+                    barrier.wait();
+                    loop {
+                        // Alternate single steals with batches drained from a local deque.
+                        let stole = if i % 2 == 0 {
+                            match stealer.steal() {
+                                Steal::Success(v) => {
+                                    got.push(v);
+                                    true
+                                }
+                                _ => false,
+                            }
+                        } else {
+                            let stole = stealer.steal_batch(&local).success().is_some();
+                            while let Some(v) = local.pop() {
+                                got.push(v);
+                            }
+                            stole
+                        };
+                        if !stole && done.load(Ordering::Acquire) && stealer.is_empty() {
+                            return got;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut got = Vec::new();
+        barrier.wait();
+        for v in 0..N {
+            worker.push(v);
+            // Pop now and then so the owner races thieves for the last elements too.
+            if v % 3 == 0
+                && let Some(v) = worker.pop()
+            {
+                got.push(v);
+            }
+        }
+        while let Some(v) = worker.pop() {
+            got.push(v);
+        }
+        done.store(true, Ordering::Release);
+        for t in thieves {
+            got.extend(t.join().unwrap());
+        }
+        got
+    });
+
+    seen.sort_unstable();
+    assert_eq!(seen, (0..N).collect::<Vec<_>>());
+}
+
+#[test]
+fn faaqueue_mpmc() {
+    const PRODUCERS: usize = 4;
+    const CONSUMERS: usize = 4;
+    const N: usize = 50_000;
+    let queue = FaaQueue::new();
+    let barrier = Barrier::new(PRODUCERS + CONSUMERS);
+
+    let popped: Vec<Vec<(usize, usize)>> = thread::scope(|s| {
+        for p in 0..PRODUCERS {
+            let (queue, barrier) = (&queue, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for i in 0..N {
+                    queue.push((p, i));
+                }
+            });
+        }
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let (queue, barrier) = (&queue, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let mut got = Vec::with_capacity(N);
+                    while got.len() < N * PRODUCERS / CONSUMERS {
+                        if let Some(v) = queue.pop() {
+                            got.push(v);
+                        }
+                    }
+                    got
+                })
+            })
+            .collect();
+        consumers.into_iter().map(|c| c.join().unwrap()).collect()
+    });
+
+    // Each consumer sees every producer's elements in the order they were pushed.
+    for got in &popped {
+        let mut last = [None; PRODUCERS];
+        for &(p, i) in got {
+            assert!(last[p] < Some(i), "producer {p} reordered");
+            last[p] = Some(i);
+        }
+    }
+    let mut all: Vec<_> = popped.into_iter().flatten().collect();
+    all.sort_unstable();
+    let expected: Vec<_> = (0..PRODUCERS)
+        .flat_map(|p| (0..N).map(move |i| (p, i)))
+        .collect();
+    assert_eq!(all, expected);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn queue_batches_stay_contiguous() {
+    const PRODUCERS: usize = 4;
+    const BATCHES: usize = 2_000;
+    const BATCH: usize = 16;
+    let queue = Queue::new();
+    let total = PRODUCERS * BATCHES * BATCH;
+
+    let mut popped = thread::scope(|s| {
+        for p in 0..PRODUCERS {
+            let queue = &queue;
+            s.spawn(move || {
+                for b in 0..BATCHES {
+                    let base = (p * BATCHES + b) * BATCH;
+                    queue.push_batch(base..base + BATCH);
+                }
+            });
+        }
+        let consumers: Vec<_> = (0..2)
+            .map(|c| {
+                let queue = &queue;
+                s.spawn(move || {
+                    let mut got = Vec::new();
+                    while got.len() < total / 2 {
+                        // Mix batch sizes with single pops so runs get split at odd places.
+                        if c == 0 {
+                            let want = (total / 2 - got.len()).min(7);
+                            got.extend(queue.pop_batch(want));
+                        } else {
+                            got.extend(queue.pop());
+                        }
+                    }
+                    got
+                })
+            })
+            .collect();
+        let got: Vec<Vec<usize>> = consumers.into_iter().map(|c| c.join().unwrap()).collect();
+
+        // A batch is pushed as one run, so any consumer sees each batch's elements in order.
+        for run in &got {
+            let mut last = vec![None; total / BATCH];
+            for &v in run {
+                assert!(last[v / BATCH] < Some(v), "batch {} reordered", v / BATCH);
+                last[v / BATCH] = Some(v);
+            }
+        }
+        got.concat()
+    });
+    popped.sort_unstable();
+    assert_eq!(popped, (0..total).collect::<Vec<_>>());
+    assert!(queue.is_empty());
+}
+
+/// Peeks race pops that go through both `pop` and `try_pop_if`: a peek only ever sees the
+/// head move forward, and every element is popped exactly once.
+fn peek_races_pop<R: Reclaimer>() {
+    const POPPERS: usize = 2;
+    const PEEKERS: usize = 2;
+    const N: usize = 100_000;
+    let queue: Queue<usize, R> = (0..N).collect();
+
+    let mut popped: Vec<usize> = thread::scope(|s| {
+        for _ in 0..PEEKERS {
+            s.spawn(|| {
+                let mut guard = R::pin();
+                let mut last = 0;
+                while let Some(&v) = queue.peek(&mut guard) {
+                    assert!(last <= v && v < N, "peeked {v} after {last}");
+                    last = v;
+                    R::repin(&mut guard);
+                }
+            });
+        }
+        let poppers: Vec<_> = (0..POPPERS)
+            .map(|p| {
+                let queue = &queue;
+                s.spawn(move || {
+                    let mut got = Vec::new();
+                    loop {
+                        let v = if p == 0 {
+                            queue.pop()
+                        } else {
+                            queue.try_pop_if(|v| v % 2 == 0).or_else(|| queue.pop())
+                        };
+                        match v {
+                            Some(v) => got.push(v),
+                            None => return got,
+                        }
+                    }
+                })
+            })
+            .collect();
+        poppers
+            .into_iter()
+            .flat_map(|p| p.join().unwrap())
+            .collect()
+    });
+    popped.sort_unstable();
+    assert_eq!(popped, (0..N).collect::<Vec<_>>());
+}
+
+#[test]
+fn queue_peek_races_pop() {
+    peek_races_pop::<Epoch>();
+    peek_races_pop::<Hazard>();
+}
+
+/// Putters and takers mixing blocking and timed calls; every item must be handed over once.
+fn rendezvous_handoff(queue: SynchronousQueue<usize>) {
+    const PAIRS: usize = 4;
+    const N: usize = 10_000;
+
+    let mut taken: Vec<usize> = thread::scope(|s| {
+        for p in 0..PAIRS {
+            let queue = &queue;
+            s.spawn(move || {
+                for i in p * N..(p + 1) * N {
+                    if i % 5 != 0 {
+                        queue.put(i);
+                        continue;
+                    }
+                    let mut item = i;
+                    while let Err(back) = queue.offer(item, Duration::from_micros(50)) {
+                        item = back;
+                    }
+                }
+            });
+        }
+        let takers: Vec<_> = (0..PAIRS)
+            .map(|t| {
+                let queue = &queue;
+                s.spawn(move || {
+                    let mut got = Vec::with_capacity(N);
+                    while got.len() < N {
+                        if t % 2 == 0 {
+                            got.push(queue.take());
+                        } else {
+                            got.extend(queue.poll(Duration::from_micros(30)));
+                        }
+                    }
+                    got
+                })
+            })
+            .collect();
+        takers.into_iter().flat_map(|t| t.join().unwrap()).collect()
+    });
+
+    taken.sort_unstable();
+    assert_eq!(taken, (0..PAIRS * N).collect::<Vec<_>>());
+    assert_eq!(queue.try_take(), None);
+}
+
+#[test]
+fn rendezvous_fair_handoff() {
+    rendezvous_handoff(SynchronousQueue::fair());
+}
+
+#[test]
+fn rendezvous_unfair_handoff() {
+    rendezvous_handoff(SynchronousQueue::unfair());
+}
+
+/// `threads` producers push and as many consumers pop on a two-lock queue guarded by `L`;
+/// every element must come out exactly once, in each producer's order.
+fn twolock_mpmc<L: RawLock>(threads: usize) {
+    const N: usize = 20_000;
+    let queue = TwoLockQueue::<(usize, usize), L>::new();
+    let barrier = Barrier::new(2 * threads);
+
+    let popped: Vec<Vec<(usize, usize)>> = thread::scope(|s| {
+        for p in 0..threads {
+            let (queue, barrier) = (&queue, &barrier);
+            s.spawn(move || {
+                barrier.wait();
+                for i in 0..N {
+                    queue.push((p, i));
+                }
+            });
+        }
+        let consumers: Vec<_> = (0..threads)
+            .map(|_| {
+                let (queue, barrier) = (&queue, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let mut got = Vec::with_capacity(N);
+                    while got.len() < N {
+                        match queue.pop() {
+                            Some(v) => got.push(v),
+                            None => thread::yield_now(),
+                        }
+                    }
+                    got
+                })
+            })
+            .collect();
+        consumers.into_iter().map(|c| c.join().unwrap()).collect()
+    });
+
+    for got in &popped {
+        let mut last = vec![None; threads];
+        for &(p, i) in got {
+            assert!(last[p] < Some(i), "producer {p} reordered");
+            last[p] = Some(i);
+        }
+    }
+    let mut all: Vec<_> = popped.into_iter().flatten().collect();
+    all.sort_unstable();
+    let expected: Vec<_> = (0..threads)
+        .flat_map(|p| (0..N).map(move |i| (p, i)))
+        .collect();
+    assert_eq!(all, expected);
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn twolock_mpmc_every_lock() {
+    twolock_mpmc::<McsParkLock>(3);
+    // The spinning locks hand over in FIFO order, so with more waiters than CPUs every
+    // handover waits for the next waiter to be scheduled again; keep them to one per CPU.
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = (cpus / 2).clamp(1, 3);
+    twolock_mpmc::<TicketLock>(threads);
+    twolock_mpmc::<Clhlock>(threads);
+    twolock_mpmc::<McsLock>(threads);
+}
+
+#[test]
+fn broadcast_backpressure_fans_out_everything() {
+    const SUBSCRIBERS: usize = 4;
+    const N: usize = 20_000;
+    let (sender, first) = broadcast::channel(16, Mode::Backpressure);
+    let barrier = Barrier::new(SUBSCRIBERS + 1);
+
+    thread::scope(|s| {
+        let mut receivers = vec![first];
+        receivers.extend((1..SUBSCRIBERS).map(|_| sender.subscribe()));
+        for mut receiver in receivers {
+            let barrier = &barrier;
+            s.spawn(move || {
+                barrier.wait();
+                for i in 0..N {
+                    assert_eq!(receiver.recv(), Ok(i));
+                }
+                assert_eq!(receiver.recv(), Err(RecvError::Closed));
+            });
+        }
+        // Moved in so it is dropped before the scope joins, closing the channel.
+        let sender = sender;
+        barrier.wait();
+        for i in 0..N {
+            // A subscriber that joins midway and leaves early must not stall the others.
+            if i == N / 2 {
+                let mut late = sender.subscribe();
+                sender.send(i).unwrap();
+                assert_eq!(late.try_recv(), Ok(i));
+                continue;
+            }
+            sender.send(i).unwrap();
+        }
+    });
+}
+
+#[test]
+fn broadcast_lag_skips_to_oldest() {
+    let (sender, mut slow) = broadcast::channel(4, Mode::Lag);
+    let mut fast = sender.subscribe();
+    for i in 0..10 {
+        sender.send(i).unwrap();
+        assert_eq!(fast.try_recv(), Ok(i));
+    }
+    // Six elements were overwritten before `slow` looked; it resumes at the oldest left.
+    assert_eq!(slow.try_recv(), Err(TryRecvError::Lagged(6)));
+    assert_eq!(slow.recv(), Ok(6));
+    assert_eq!(fast.try_recv(), Err(TryRecvError::Empty));
+    drop((fast, slow));
+    assert!(sender.send(10).is_err());
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Minimal executor: polls `f` on this thread, parking between wakeups.
+fn block_on<F: Future>(f: F) -> F::Output {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut f = pin!(f);
+    loop {
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(t) => return t,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn asyncqueue_wakes_waiting_pops() {
+    const PRODUCERS: usize = 2;
+    const CONSUMERS: usize = 4;
+    const N: usize = 20_000;
+    let queue = AsyncQueue::new();
+
+    let mut popped: Vec<usize> = thread::scope(|s| {
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|c| {
+                let queue = &queue;
+                s.spawn(move || {
+                    let count = PRODUCERS * N / CONSUMERS;
+                    if c % 2 == 0 {
+                        (0..count).map(|_| block_on(queue.pop())).collect()
+                    } else {
+                        let mut stream = queue.stream();
+                        let next = std::future::poll_fn(|cx| stream.poll_next_item(cx));
+                        let mut next = pin!(next);
+                        (0..count)
+                            .map(|_| block_on(next.as_mut()))
+                            .collect::<Vec<_>>()
+                    }
+                })
+            })
+            .collect();
+        for p in 0..PRODUCERS {
+            let queue = &queue;
+            s.spawn(move || {
+                for i in p * N..(p + 1) * N {
+                    queue.push(i);
+                    if i % 64 == 0 {
+                        // Let consumers drain and go back to waiting now and then.
+                        thread::yield_now();
+                    }
+                }
+            });
+        }
+        consumers
+            .into_iter()
+            .flat_map(|c| c.join().unwrap())
+            .collect()
+    });
+
+    popped.sort_unstable();
+    assert_eq!(popped, (0..PRODUCERS * N).collect::<Vec<_>>());
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn queue_snapshots_race_pops() {
+    // Snapshots taken while another thread pops stay in FIFO order and never invent values.
+    const N: usize = 100_000;
+    let queue: Queue<usize> = (0..N).collect();
+    thread::scope(|s| {
+        s.spawn(|| while queue.pop().is_some() {});
+        while !queue.is_empty() {
+            let guard = crossbeam_epoch::pin();
+            let seen: Vec<_> = queue.iter(&guard).take(1000).copied().collect();
+            assert!(seen.windows(2).all(|w| w[0] + 1 == w[1]));
+            assert!(seen.iter().all(|&v| v < N));
+        }
+    });
+}
+
+#[test]
+fn elimination_stack_pairs_and_drains() {
+    const THREADS: usize = 4;
+    const N: usize = 50_000;
+    let stack = EliminationStack::new();
+    let barrier = Barrier::new(THREADS);
+
+    let mut popped: Vec<usize> = thread::scope(|s| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (stack, barrier) = (&stack, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let mut got = Vec::new();
+                    // Push and pop in lockstep so pops keep meeting pushes in flight.
+                    for i in t * N..(t + 1) * N {
+                        stack.push(i);
+                        got.extend(stack.pop());
+                    }
+                    got
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect()
+    });
+
+    // Each pop follows its own thread's push, so no pop ever finds the stack empty.
+    assert_eq!(popped.len(), THREADS * N);
+    popped.sort_unstable();
+    assert_eq!(popped, (0..THREADS * N).collect::<Vec<_>>());
+    assert!(stack.is_empty());
+    assert!((1..=16).contains(&stack.width()));
+}
+
+#[test]
+fn exchanger_swaps_in_pairs() {
+    const THREADS: usize = 4;
+    const N: usize = 10_000;
+    let exchanger = Exchanger::new();
+
+    let results: Vec<(Vec<usize>, Vec<usize>)> = thread::scope(|s| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let exchanger = &exchanger;
+                s.spawn(move || {
+                    let (mut got, mut kept) = (Vec::new(), Vec::new());
+                    for i in t * N..(t + 1) * N {
+                        match exchanger.exchange(i, Duration::from_micros(100)) {
+                            Ok(v) => got.push(v),
+                            Err(v) => kept.push(v),
+                        }
+                    }
+                    (got, kept)
+                })
+            })
+            .collect();
+        workers.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+
+    // Every value either reached exactly one partner or came back to its own thread.
+    let mut all: Vec<usize> = Vec::new();
+    for (t, (got, kept)) in results.iter().enumerate() {
+        assert!(got.iter().all(|v| !(t * N..(t + 1) * N).contains(v)));
+        all.extend(got);
+        all.extend(kept);
+    }
+    all.sort_unstable();
+    assert_eq!(all, (0..THREADS * N).collect::<Vec<_>>());
+}
+
+#[test]
+fn stack_push_all_take_all() {
+    const PRODUCERS: usize = 3;
+    const BATCHES: usize = 2_000;
+    const BATCH: usize = 8;
+    let total = PRODUCERS * BATCHES * BATCH;
+    let stack = Stack::new();
+    let done = AtomicBool::new(false);
+
+    let mut seen = thread::scope(|s| {
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|p| {
+                let stack = &stack;
+                s.spawn(move || {
+                    for b in 0..BATCHES {
+                        let base = (p * BATCHES + b) * BATCH;
+                        stack.push_all(base..base + BATCH);
+                    }
+                })
+            })
+            .collect();
+        let consumer = s.spawn(|| {
+            let mut seen = Vec::new();
+            loop {
+                let finished = done.load(Ordering::Acquire);
+                let taken: Vec<usize> = stack.take_all().collect();
+                // A batch is published and detached whole, so it comes out as one
+                // descending run.
+                for run in taken.chunk_by(|a, b| a / BATCH == b / BATCH) {
+                    assert_eq!(run.len(), BATCH, "batch split across take_all calls");
+                    assert!(run.windows(2).all(|w| w[0] == w[1] + 1));
+                }
+                seen.extend(taken);
+                if finished {
+                    return seen;
+                }
+            }
+        });
+        for p in producers {
+            p.join().unwrap();
+        }
+        done.store(true, Ordering::Release);
+        consumer.join().unwrap()
+    });
+    seen.sort_unstable();
+    assert_eq!(seen, (0..total).collect::<Vec<_>>());
+}
+
+#[test]
+fn tagged_stack_recycles_nodes() {
+    const THREADS: usize = 4;
+    const N: usize = 50_000;
+    let stack = TaggedStack::with_capacity(THREADS);
+    let barrier = Barrier::new(THREADS);
+
+    // Tight push/pop pairs keep handing the same few nodes around, which is where ABA bites.
+    let mut popped: Vec<usize> = thread::scope(|s| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (stack, barrier) = (&stack, &barrier);
+                s.spawn(move || {
+                    barrier.wait();
+                    let mut got = Vec::with_capacity(N);
+                    for i in t * N..(t + 1) * N {
+                        stack.push(i);
+                        got.push(stack.pop().expect("pop after own push found it empty"));
+                    }
+                    got
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect()
+    });
+    popped.sort_unstable();
+    assert_eq!(popped, (0..THREADS * N).collect::<Vec<_>>());
+    assert!(stack.is_empty());
+}
+
+#[test]
+fn hazard_protected_nodes_outlive_retire() {
+    const READERS: usize = 3;
+    const SWAPS: usize = 20_000;
+    let item = Arc::new(());
+    let domain = Domain::with_threshold(16);
+    let src = AtomicPtr::new(Box::into_raw(Box::new((0, Arc::clone(&item)))));
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|| {
+                let mut hp = HazardPointer::new(&domain);
+                let mut last = 0;
+                while !done.load(Ordering::Acquire) {
+                    let node = unsafe { &*hp.protect(&src) };
+                    // A freed node would have dropped its clone and could read anything.
+                    assert!(node.0 >= last && Arc::strong_count(&node.1) > 1);
+                    last = node.0;
+                }
+            });
+        }
+        for i in 1..=SWAPS {
+            let new = Box::into_raw(Box::new((i, Arc::clone(&item))));
+            let old = src.swap(new, Ordering::AcqRel);
+            unsafe { domain.retire(old) };
+        }
+        done.store(true, Ordering::Release);
+    });
+
+    // With the readers gone, a scan frees all but what one slot still names.
+    let mut hp = HazardPointer::new(&domain);
+    let kept = hp.protect(&src);
+    let old = src.swap(ptr::null_mut(), Ordering::AcqRel);
+    unsafe { domain.retire(old) };
+    domain.scan();
+    assert_eq!(unsafe { (*kept).0 }, SWAPS);
+    assert_eq!(Arc::strong_count(&item), 2);
+    hp.reset();
+    assert_eq!(domain.scan(), 1);
+    assert_eq!(Arc::strong_count(&item), 1);
+    drop(hp);
+
+    // The global domain hands a dropped pointer's slot to the thread's next one.
+    let global = Domain::global();
+    let node = Box::into_raw(Box::new((0, Arc::clone(&item))));
+    let src = AtomicPtr::new(node);
+    drop(HazardPointer::new(global));
+    let mut hp = HazardPointer::new(global);
+    hp.protect(&src);
+    unsafe { global.retire(src.swap(ptr::null_mut(), Ordering::AcqRel)) };
+    global.scan();
+    assert_eq!(Arc::strong_count(&item), 2);
+    drop(hp);
+    scan_global_until(|| Arc::strong_count(&item) == 1);
+}
+
+/// Pushes and pops across threads on a stack and a queue, and inserts and deletes
+/// overlapping keys in a list, all freeing through `R`. Each thread calls `step` between
+/// operations.
+fn structures_over<R: Reclaimer>(step: fn()) {
+    const THREADS: usize = 4;
+    const N: usize = 20_000;
+    let stack = Stack::<usize, R>::with_reclaimer();
+    let queue = Queue::<usize, R>::with_reclaimer();
+    let list = List::<usize, usize, R>::with_reclaimer();
+
+    let mut popped: Vec<usize> = thread::scope(|s| {
+        let workers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let (stack, queue, list) = (&stack, &queue, &list);
+                s.spawn(move || {
+                    let mut got = Vec::with_capacity(2 * N);
+                    for i in t * N..(t + 1) * N {
+                        step();
+                        stack.push(i);
+                        queue.push(i);
+                        got.extend(stack.pop());
+                        got.extend(queue.pop());
+
+                        // Keys interleave across threads, so every insert and delete races
+                        // with the neighbours' unlinking around it.
+                        let mut guard = R::pin();
+                        let key = i % 64 * THREADS + t;
+                        assert!(list.harris_michael_insert(key, i, &guard));
+                        assert_eq!(list.harris_michael_lookup(&key, &mut guard), Some(&i));
+                        assert_eq!(list.harris_michael_delete(&key, &mut guard), Some(&i));
+                    }
+                    got
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect()
+    });
+    popped.sort_unstable();
+    let expected: Vec<usize> = (0..THREADS * N).flat_map(|i| [i, i]).collect();
+    assert_eq!(popped, expected);
+    assert!(stack.is_empty() && queue.is_empty());
+}
+
+#[test]
+fn reclaimers_run_stack_queue_and_list() {
+    structures_over::<Hazard>(|| {});
+    structures_over::<Leak>(|| {});
+    // The checks at the end read from this thread too.
+    qsbr::register();
+    structures_over::<Qsbr>(|| {
+        qsbr::register();
+        qsbr::quiescent();
+    });
+    qsbr::unregister();
+}
+
+#[test]
+fn qsbr_frees_after_every_online_thread_is_quiescent() {
+    let item = Arc::new(());
+    let domain = qsbr::Domain::new();
+    let (reader, writer) = (domain.register(), domain.register());
+
+    unsafe { writer.retire(Box::into_raw(Box::new(Arc::clone(&item)))) };
+    writer.quiescent();
+    assert_eq!(
+        Arc::strong_count(&item),
+        2,
+        "freed before the reader was quiescent"
+    );
+    reader.quiescent();
+    assert_eq!(writer.reclaim(), 1);
+    assert_eq!(Arc::strong_count(&item), 1);
+
+    // An offline thread does not hold up the grace period.
+    reader.offline();
+    unsafe { writer.retire(Box::into_raw(Box::new(Arc::clone(&item)))) };
+    writer.quiescent();
+    assert_eq!(Arc::strong_count(&item), 1);
+
+    // Retires left behind by a dropped registration are freed by the others.
+    reader.online();
+    unsafe { writer.retire(Box::into_raw(Box::new(Arc::clone(&item)))) };
+    drop(writer);
+    assert_eq!(Arc::strong_count(&item), 2);
+    reader.quiescent();
+    reader.reclaim();
+    assert_eq!(Arc::strong_count(&item), 1);
+}
+
+/// Global allocator that counts the blocks allocated by threads inside [`tracked`] and not
+/// freed yet, wherever they end up being freed.
+///
+/// Every block carries a prefix recording whether it was tracked, so a node pushed on one
+/// thread and reclaimed on another is still accounted for, and allocations of other tests
+/// running alongside are left out.
+struct CountingAlloc;
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+static TRACKED_LIVE: AtomicIsize = AtomicIsize::new(0);
+
+thread_local! {
+    static TRACKING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Room for the tracked flag in front of a block, keeping the block's alignment.
+fn with_prefix(layout: Layout) -> (Layout, usize) {
+    let prefix = layout.align().max(16);
+    let outer = Layout::from_size_align(layout.size() + prefix, prefix).unwrap();
+    (outer, prefix)
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (outer, prefix) = with_prefix(layout);
+        let base = unsafe { System.alloc(outer) };
+        if base.is_null() {
+            return base;
+        }
+        let tracked = TRACKING.try_with(Cell::get).unwrap_or(false);
+        if tracked {
+            TRACKED_LIVE.fetch_add(1, Ordering::Relaxed);
+        }
+        let ptr = unsafe { base.add(prefix) };
+        unsafe { ptr.cast::<usize>().sub(1).write(usize::from(tracked)) };
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (outer, prefix) = with_prefix(layout);
+        if unsafe { ptr.cast::<usize>().sub(1).read() } == 1 {
+            TRACKED_LIVE.fetch_sub(1, Ordering::Relaxed);
+        }
+        unsafe { System.dealloc(ptr.sub(prefix), outer) };
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // The prefix moves along with the block, so it stays counted as it was.
+        let (outer, prefix) = with_prefix(layout);
+        let base = unsafe { System.realloc(ptr.sub(prefix), outer, new_size + prefix) };
+        if base.is_null() {
+            return base;
+        }
+        unsafe { base.add(prefix) }
+    }
+}
+
+/// Runs `f` with this thread's allocations counted by [`CountingAlloc`].
+fn tracked<T>(f: impl FnOnce() -> T) -> T {
+    // Register with the epoch first, so the thread's record, which outlives the test, is
+    // not counted.
+    drop(crossbeam_epoch::pin());
+    TRACKING.set(true);
+    let t = f();
+    TRACKING.set(false);
+    t
+}
+
+/// The accounting tests share [`TRACKED_LIVE`], so they take turns.
+static ACCOUNTING: Mutex<()> = Mutex::new(());
+
+/// Flushes the epoch until every tracker is dropped and the tracked blocks are back to
+/// `live_before`, then checks that each was dropped exactly once and that nothing leaked.
+fn assert_all_reclaimed(counts: &DropCounts, live_before: isize) {
+    let created = counts.created();
+    for _ in 0..100_000 {
+        if counts.dropped() == created && TRACKED_LIVE.load(Ordering::Relaxed) == live_before {
+            break;
+        }
+        // Other tests may hold the epoch back for a moment.
+        crossbeam_epoch::pin().flush();
+        thread::yield_now();
+    }
+    counts.assert_all_dropped();
+    let leaked = TRACKED_LIVE.load(Ordering::Relaxed) - live_before;
+    assert_eq!(leaked, 0, "blocks leaked");
+}
+
+const TRACKED_THREADS: usize = 4;
+const TRACKED_N: usize = 5_000;
+
+#[test]
+fn stack_drops_and_frees_everything() {
+    let _turn = ACCOUNTING.lock().unwrap_or_else(PoisonError::into_inner);
+    let live_before = TRACKED_LIVE.load(Ordering::Relaxed);
+    let counts = DropCounts::new(TRACKED_THREADS * TRACKED_N * 2);
+
+    tracked(|| {
+        let stack = Stack::new();
+        thread::scope(|s| {
+            for t in 0..TRACKED_THREADS {
+                let (stack, counts) = (&stack, &counts);
+                s.spawn(move || {
+                    tracked(|| {
+                        for i in 0..TRACKED_N {
+                            match i % 4 {
+                                0 => stack.push_all((0..2).map(|k| counts.track(t + k))),
+                                1 => drop(stack.take_all().take(3)),
+                                _ => {
+                                    stack.push(counts.track(t));
+                                    drop(stack.pop());
+                                }
+                            }
+                        }
+                    })
+                });
+            }
+        });
+        for v in 0..TRACKED_N {
+            stack.push(counts.track(v));
+        }
+        drop(stack.pop());
+        // Dropped with most of its elements still in it.
+    });
+    assert_all_reclaimed(&counts, live_before);
+}
+
+#[test]
+fn queue_drops_and_frees_everything() {
+    let _turn = ACCOUNTING.lock().unwrap_or_else(PoisonError::into_inner);
+    let live_before = TRACKED_LIVE.load(Ordering::Relaxed);
+    let counts = DropCounts::new(TRACKED_THREADS * TRACKED_N * 4);
+
+    tracked(|| {
+        let queue = Queue::new();
+        thread::scope(|s| {
+            for t in 0..TRACKED_THREADS {
+                let (queue, counts) = (&queue, &counts);
+                s.spawn(move || {
+                    tracked(|| {
+                        for i in 0..TRACKED_N {
+                            if i % 2 == 0 {
+                                queue.push_batch((0..3).map(|k| counts.track(t + k)));
+                                drop(queue.pop_batch(2));
+                            } else {
+                                queue.push(counts.track(t));
+                                drop(queue.pop());
+                            }
+                        }
+                    })
+                });
+            }
+        });
+        drop(queue.pop());
+    });
+    assert_all_reclaimed(&counts, live_before);
+}
+
+#[test]
+fn list_drops_keys_values_and_frees_everything() {
+    const KEYS: usize = 256;
+    let _turn = ACCOUNTING.lock().unwrap_or_else(PoisonError::into_inner);
+    let live_before = TRACKED_LIVE.load(Ordering::Relaxed);
+    let counts = DropCounts::new(TRACKED_THREADS * TRACKED_N * 5);
+
+    tracked(|| {
+        let list = List::new();
+        thread::scope(|s| {
+            for t in 0..TRACKED_THREADS {
+                let (list, counts) = (&list, &counts);
+                s.spawn(move || {
+                    tracked(|| {
+                        for i in 0..TRACKED_N {
+                            // Threads collide on keys, so inserts also fail and hand their node
+                            // back, and deletes race with unlinking.
+                            let key = (i * 7 + t) % KEYS;
+                            let guard = &mut crossbeam_epoch::pin();
+                            list.harris_michael_insert(counts.track(key), counts.track(i), guard);
+                            let probe = counts.track((key + KEYS / 2) % KEYS);
+                            list.harris_michael_delete(&probe, guard);
+                            list.harris_michael_lookup(&counts.track(key), guard);
+                        }
+                    })
+                });
+            }
+        });
+        // Dropped with up to `KEYS` nodes still linked, some of them marked.
+    });
+    assert_all_reclaimed(&counts, live_before);
+}
@@ -0,0 +1,35 @@
+//! Synchronization primitives used by the locks and the queue's parking slow path, switched
+//! to loom's model-checked versions when the crate is built with `--cfg loom`.
+//!
+//! The epoch-based structures get the same treatment from crossbeam-epoch itself, which swaps
+//! its atomics for loom's under `--cfg crossbeam_loom` with its `loom` feature enabled, so a
+//! model-checking run needs both cfgs:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom --cfg crossbeam_loom" cargo test --release --lib loomtest
+//! ```
+
+#[cfg(loom)]
+pub(crate) use loom::{
+    hint,
+    sync::{Condvar, Mutex, RwLock, atomic},
+    thread,
+};
+#[cfg(not(loom))]
+pub(crate) use std::{
+    hint,
+    sync::{Condvar, Mutex, RwLock, atomic},
+    thread,
+};
+
+/// `thread::park_timeout`, which loom lacks; there a yield stands in for the timed park,
+/// which callers must already treat as a possibly spurious wakeup.
+#[cfg(not(loom))]
+pub(crate) fn park_timeout(timeout: std::time::Duration) {
+    thread::park_timeout(timeout);
+}
+
+#[cfg(loom)]
+pub(crate) fn park_timeout(_timeout: std::time::Duration) {
+    thread::yield_now();
+}
@@ -0,0 +1,274 @@
+use std::{cell::UnsafeCell, marker::PhantomData, mem::MaybeUninit, ptr};
+
+use crate::sync::atomic::{AtomicPtr, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+/// Nodes in the first chunk of an [`Arena`]; each later chunk doubles the one before.
+const FIRST_CHUNK: usize = 32;
+/// Enough doubling chunks for every node a 32-bit index can name.
+const CHUNKS: usize = 28;
+
+/// Stands for "no node" where a node index is expected.
+const NIL: u32 = 0;
+
+struct Node<T> {
+    data: UnsafeCell<MaybeUninit<T>>,
+    /// Index of the node below. Atomic because a pop may read it from a node another thread
+    /// has just taken and is relinking; that pop's CAS then fails on the tag.
+    next: AtomicU32,
+}
+
+/// Where a stack's nodes live. They are named by a 32-bit index rather than by address, so
+/// the word a list CASes has the other 32 bits for its tag whatever the platform's address
+/// width. Chunks are only added, never moved or freed before the arena, so a node stays
+/// readable through its index for as long as the stack lives.
+struct Arena<T> {
+    chunks: [AtomicPtr<Node<T>>; CHUNKS],
+    /// Nodes handed out so far; node `i` has index `i + 1`, leaving 0 for [`NIL`].
+    len: AtomicUsize,
+}
+
+/// The chunk holding node `n`, and its offset there.
+fn locate(n: usize) -> (usize, usize) {
+    let chunk = (n / FIRST_CHUNK + 1).ilog2() as usize;
+    (chunk, n - FIRST_CHUNK * ((1 << chunk) - 1))
+}
+
+fn chunk_len(chunk: usize) -> usize {
+    FIRST_CHUNK << chunk
+}
+
+impl<T> Arena<T> {
+    fn new() -> Self {
+        Self {
+            chunks: std::array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    fn alloc(&self) -> u32 {
+        let n = self.len.fetch_add(1, Ordering::Relaxed);
+        let index = u32::try_from(n + 1)
+            .ok()
+            .filter(|&i| i != u32::MAX)
+            .expect("a tagged stack holds fewer than u32::MAX nodes");
+        let (chunk, _) = locate(n);
+        if self.chunks[chunk].load(Ordering::Acquire).is_null() {
+            let nodes: Box<[Node<T>]> = (0..chunk_len(chunk))
+                .map(|_| Node {
+                    data: UnsafeCell::new(MaybeUninit::uninit()),
+                    next: AtomicU32::new(NIL),
+                })
+                .collect();
+            let nodes = Box::into_raw(nodes).cast::<Node<T>>();
+            if self.chunks[chunk]
+                .compare_exchange(ptr::null_mut(), nodes, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+            {
+                // Another allocation in this chunk installed it first.
+                drop(unsafe { Self::chunk_from_raw(nodes, chunk) });
+            }
+        }
+        index
+    }
+
+    /// `index` must come from this arena's `alloc`.
+    fn node(&self, index: u32) -> &Node<T> {
+        let (chunk, offset) = locate(index as usize - 1);
+        unsafe { &*self.chunks[chunk].load(Ordering::Acquire).add(offset) }
+    }
+
+    unsafe fn chunk_from_raw(nodes: *mut Node<T>, chunk: usize) -> Box<[Node<T>]> {
+        unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(nodes, chunk_len(chunk))) }
+    }
+}
+
+impl<T> Drop for Arena<T> {
+    /// Frees the nodes only; the stack drops the elements still in them first.
+    fn drop(&mut self) {
+        for (chunk, nodes) in self.chunks.iter().enumerate() {
+            let nodes = nodes.load(Ordering::Relaxed);
+            if !nodes.is_null() {
+                drop(unsafe { Self::chunk_from_raw(nodes, chunk) });
+            }
+        }
+    }
+}
+
+fn pack(index: u32, tag: u32) -> u64 {
+    u64::from(tag) << 32 | u64::from(index)
+}
+
+fn unpack(word: u64) -> (u32, u32) {
+    (word as u32, (word >> 32) as u32)
+}
+
+/// Treiber list of arena nodes whose head carries a counter bumped by every successful CAS,
+/// so a head that was popped and pushed back in between no longer compares equal.
+struct TaggedList<T> {
+    head: AtomicU64,
+    _marker: PhantomData<*mut Node<T>>,
+}
+
+impl<T> TaggedList<T> {
+    fn new() -> Self {
+        Self {
+            head: AtomicU64::new(pack(NIL, 0)),
+            _marker: PhantomData,
+        }
+    }
+
+    fn push(&self, arena: &Arena<T>, index: u32) {
+        let node = arena.node(index);
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            let (top, tag) = unpack(head);
+            node.next.store(top, Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                pack(index, tag.wrapping_add(1)),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    fn pop(&self, arena: &Arena<T>) -> Option<u32> {
+        let mut head = self.head.load(Ordering::Acquire);
+        loop {
+            let (top, tag) = unpack(head);
+            if top == NIL {
+                return None;
+            }
+            // `top` may already be popped and reused, but its node is never freed: the read
+            // is of a live node, and a stale `next` is caught by the tag.
+            let next = arena.node(top).next.load(Ordering::Relaxed);
+            match self.head.compare_exchange_weak(
+                head,
+                pack(next, tag.wrapping_add(1)),
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Some(top),
+                Err(current) => head = current,
+            }
+        }
+    }
+}
+
+/// Treiber stack that defeats ABA with a tag packed next to the head, instead of relying on
+/// `crossbeam_epoch` like [`Stack`](crate::treiberstack::Stack).
+///
+/// Popped nodes go to a free list of their own and are reused by later pushes; they are only
+/// returned to the allocator when the stack is dropped. Memory a pop may still be reading is
+/// therefore always a node of this stack, so no reclamation scheme is needed, and the stack
+/// only uses atomics and `alloc`. The price is that the stack's footprint never shrinks
+/// below its high-water mark. Nodes are named by 32-bit indices into the stack's own arena,
+/// which leaves a 32-bit tag: a pop can only be fooled by a stale head if it is preempted
+/// between its load and its CAS for 2^32 successful operations on the same list.
+pub struct TaggedStack<T> {
+    arena: Arena<T>,
+    head: TaggedList<T>,
+    free: TaggedList<T>,
+}
+
+unsafe impl<T: Send> Send for TaggedStack<T> {}
+unsafe impl<T: Send> Sync for TaggedStack<T> {}
+
+impl<T> Default for TaggedStack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> TaggedStack<T> {
+    pub fn new() -> Self {
+        Self {
+            arena: Arena::new(),
+            head: TaggedList::new(),
+            free: TaggedList::new(),
+        }
+    }
+
+    /// Creates a stack with `capacity` nodes already on its free list, so the first
+    /// `capacity` pushes do not allocate.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let stack = Self::new();
+        for _ in 0..capacity {
+            stack.free.push(&stack.arena, stack.arena.alloc());
+        }
+        stack
+    }
+
+    pub fn push(&self, t: T) {
+        let index = self
+            .free
+            .pop(&self.arena)
+            .unwrap_or_else(|| self.arena.alloc());
+        unsafe { (*self.arena.node(index).data.get()).write(t) };
+        self.head.push(&self.arena, index);
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let index = self.head.pop(&self.arena)?;
+        let t = unsafe { (*self.arena.node(index).data.get()).assume_init_read() };
+        self.free.push(&self.arena, index);
+        Some(t)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        unpack(self.head.head.load(Ordering::Acquire)).0 == NIL
+    }
+}
+
+impl<T> Drop for TaggedStack<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn drop_drops_what_is_left() {
+        let counts = DropCounts::new(100);
+        let stack = TaggedStack::with_capacity(4);
+        for i in 0..100 {
+            stack.push(counts.track(i));
+        }
+        assert_eq!(stack.pop().map(|t| t.value), Some(99));
+        assert_eq!(counts.dropped(), 1);
+        drop(stack);
+        counts.assert_all_dropped();
+    }
+
+    #[test]
+    fn locate_walks_doubling_chunks() {
+        assert_eq!(locate(0), (0, 0));
+        assert_eq!(locate(FIRST_CHUNK - 1), (0, FIRST_CHUNK - 1));
+        assert_eq!(locate(FIRST_CHUNK), (1, 0));
+        assert_eq!(locate(3 * FIRST_CHUNK), (2, 0));
+        let last = u32::MAX as usize - 2;
+        let (chunk, offset) = locate(last);
+        assert!(chunk < CHUNKS && offset < chunk_len(chunk));
+    }
+
+    #[test]
+    fn tag_wraps_without_disturbing_the_index() {
+        let stack = TaggedStack::new();
+        stack.push(1);
+        let (index, _) = unpack(stack.head.head.load(Ordering::Relaxed));
+        stack
+            .head
+            .head
+            .store(pack(index, u32::MAX), Ordering::Relaxed);
+        assert_eq!(stack.pop(), Some(1));
+        assert_eq!(unpack(stack.head.head.load(Ordering::Relaxed)), (NIL, 0));
+        assert!(stack.is_empty());
+    }
+}
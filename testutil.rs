@@ -0,0 +1,116 @@
+//! Helpers shared by the unit tests next to each structure and the suites in `stresstest`.
+
+use std::{
+    cmp,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use crate::hazard::Domain;
+
+/// Shared record of every [`DropTracker`] made from it.
+pub(crate) struct DropCounts {
+    created: AtomicUsize,
+    dropped: Box<[AtomicBool]>,
+    /// Drops of a value that was already dropped; counted rather than panicking, since a
+    /// drop may run inside the epoch's deferred functions.
+    twice: AtomicUsize,
+}
+
+impl DropCounts {
+    pub(crate) fn new(capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            created: AtomicUsize::new(0),
+            dropped: (0..capacity).map(|_| AtomicBool::new(false)).collect(),
+            twice: AtomicUsize::new(0),
+        })
+    }
+
+    pub(crate) fn track(self: &Arc<Self>, value: usize) -> DropTracker {
+        let id = self.created.fetch_add(1, Ordering::Relaxed);
+        assert!(id < self.dropped.len(), "more trackers than capacity");
+        DropTracker {
+            value,
+            id,
+            counts: Arc::clone(self),
+        }
+    }
+
+    pub(crate) fn created(&self) -> usize {
+        self.created.load(Ordering::Relaxed)
+    }
+
+    /// Whether the `id`-th tracker made from this record was dropped.
+    pub(crate) fn is_dropped(&self, id: usize) -> bool {
+        self.dropped[id].load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped
+            .iter()
+            .filter(|d| d.load(Ordering::Relaxed))
+            .count()
+    }
+
+    /// Checks that every tracker made so far was dropped, and none of them twice.
+    pub(crate) fn assert_all_dropped(&self) {
+        assert_eq!(
+            self.twice.load(Ordering::Relaxed),
+            0,
+            "values dropped twice"
+        );
+        assert_eq!(self.dropped(), self.created(), "values never dropped");
+    }
+}
+
+/// A value that records its own drop in [`DropCounts`]. Compares by `value`, so it can serve
+/// as a list key; every tracker has its own id regardless.
+pub(crate) struct DropTracker {
+    pub(crate) value: usize,
+    id: usize,
+    counts: Arc<DropCounts>,
+}
+
+impl Drop for DropTracker {
+    fn drop(&mut self) {
+        if self.counts.dropped[self.id].swap(true, Ordering::Relaxed) {
+            self.counts.twice.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl PartialEq for DropTracker {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for DropTracker {}
+
+impl PartialOrd for DropTracker {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for DropTracker {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+/// Scans the global hazard domain until `done`. Other tests share the domain, and one of
+/// their scans may be holding our retired nodes for a moment.
+pub(crate) fn scan_global_until(done: impl Fn() -> bool) {
+    for _ in 0..1_000 {
+        Domain::global().scan();
+        if done() {
+            return;
+        }
+        thread::yield_now();
+    }
+    panic!("retired nodes were never freed");
+}
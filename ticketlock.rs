@@ -1,10 +1,12 @@
-use std::{
-    sync::{Arc, atomic::AtomicUsize},
-    thread,
-    time::Instant,
+use crate::{
+    rawlock::RawLock,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        hint,
+    },
 };
 
-struct TicketLock {
+pub struct TicketLock {
     current: AtomicUsize,
     next: AtomicUsize,
 }
@@ -17,43 +19,32 @@ impl TicketLock {
         }
     }
     pub fn lock(&self) -> usize {
-        let ticket = self.next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-        while ticket != self.current.load(std::sync::atomic::Ordering::Acquire) {
-            std::hint::spin_loop();
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        while ticket != self.current.load(Ordering::Acquire) {
+            hint::spin_loop();
         }
         ticket
     }
     pub fn unlock(&self, ticket: usize) {
         self.current
-            .store(ticket.wrapping_add(1), std::sync::atomic::Ordering::Release);
+            .store(ticket.wrapping_add(1), Ordering::Release);
     }
 }
 
-pub fn a() {
-    let a = Arc::new(TicketLock::new());
-    let b = Arc::new(AtomicUsize::new(0));
-    let mut handles = Vec::new();
-    for _ in 0..8 {
-        let a = Arc::clone(&a);
-        let b = Arc::clone(&b);
-        let hand = thread::spawn(move || {
-            for _ in 0..100 {
-                let k = a.lock();
-                b.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                a.unlock(k);
-            }
-        });
-        handles.push(hand);
+impl Default for TicketLock {
+    fn default() -> Self {
+        Self::new()
     }
-    let start = Instant::now();
-    for h in handles {
-        h.join().unwrap();
+}
+
+impl RawLock for TicketLock {
+    type Token = usize;
+
+    fn lock(&self) -> usize {
+        TicketLock::lock(self)
+    }
+
+    fn unlock(&self, token: usize) {
+        TicketLock::unlock(self, token)
     }
-    let duration = start.elapsed();
-    println!(
-        "Expected:{:?} completed at Duration {:?}",
-        8 * 100,
-        duration
-    );
-    println!("Actual:{:?}", b.load(std::sync::atomic::Ordering::Relaxed));
 }
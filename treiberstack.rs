@@ -1,18 +1,37 @@
 use std::{
-    mem::{self, MaybeUninit},
+    marker::PhantomData,
+    mem::MaybeUninit,
     ptr,
-    sync::{atomic::Ordering, Arc}, thread::{self, JoinHandle},
+    sync::Arc, thread::{self, JoinHandle},
 };
 
-use crossbeam_epoch::{Atomic, Owned, Shared};
+use crate::{
+    reclaim::{Epoch, Optimistic, Reclaimer},
+    sync::atomic::{AtomicPtr, Ordering},
+};
 
 pub struct Node<T> {
     data: MaybeUninit<T>,
     next: *const Node<T>,
 }
 
-pub struct Stack<T> {
-    head: Atomic<Node<T>>,
+impl<T> Node<T> {
+    pub(crate) fn new(t: T) -> Box<Self> {
+        Box::new(Node {
+            data: MaybeUninit::new(t),
+            next: ptr::null(),
+        })
+    }
+
+    /// Moves the element out of a node taken off the stack by someone who now owns it.
+    pub(crate) unsafe fn take(&self) -> T {
+        unsafe { self.data.assume_init_read() }
+    }
+}
+
+pub struct Stack<T, R: Reclaimer = Epoch> {
+    head: AtomicPtr<Node<T>>,
+    _reclaimer: PhantomData<R>,
 }
 
 pub fn tr( ) {
@@ -40,74 +59,314 @@ pub fn tr( ) {
     println!("Stack:{:?}",a.is_empty())
 }
 
-unsafe impl<T> Send for Stack<T> where T: Send {}
-unsafe impl<T> Sync for Stack<T> where T: Send {}
+unsafe impl<T, R: Reclaimer> Send for Stack<T, R> where T: Send {}
+unsafe impl<T, R: Reclaimer> Sync for Stack<T, R> where T: Send {}
 
 impl<T> Stack<T> {
     pub fn new() -> Stack<T> {
+        Self::with_reclaimer()
+    }
+}
+
+impl<T, R: Reclaimer> Default for Stack<T, R> {
+    fn default() -> Self {
+        Self::with_reclaimer()
+    }
+}
+
+impl<T, R: Reclaimer> Stack<T, R> {
+    /// Creates a stack that frees popped nodes through `R` instead of the default epoch.
+    pub fn with_reclaimer() -> Self {
         Stack {
-            head: Atomic::null(),
+            head: AtomicPtr::new(ptr::null_mut()),
+            _reclaimer: PhantomData,
         }
     }
     pub fn push(&self, t: T) {
-        let mut node = Owned::new(Node {
-            data: MaybeUninit::new(t),
-            next: ptr::null(),
-        });
-        let guard = unsafe { crossbeam_epoch::unprotected() };
-        let mut top = self.head.load(Ordering::Relaxed, &guard);
+        let node = Box::into_raw(Node::new(t));
+        // Nothing is dereferenced but our own node, so no guard is needed.
+        let mut top = self.head.load(Ordering::Relaxed);
         loop {
-            node.next = top.as_raw();
-            match self.head.compare_exchange(
-                top,
-                node,
-                Ordering::Release,
-                Ordering::Relaxed,
-                &guard,
-            ) {
+            unsafe { (*node).next = top };
+            match self
+                .head
+                .compare_exchange(top, node, Ordering::Release, Ordering::Relaxed)
+            {
                 Ok(_) => break,
-                Err(e) => {
-                    top = e.current;
-                    node = e.new;
-                }
+                Err(current) => top = current,
             }
         }
     }
     pub fn pop(&self) -> Option<T> {
-        let mut guard = crossbeam_epoch::pin();
+        let mut guard = R::pin();
         loop {
-            let top = self.head.load(Ordering::Acquire, &guard);
+            let top = R::protect(&guard, 0, &self.head);
             let t = unsafe { top.as_ref()? };
-            let next = Shared::from(t.next);
             if self
                 .head
-                .compare_exchange(top, next, Ordering::Release, Ordering::Relaxed, &guard)
+                .compare_exchange(top, t.next.cast_mut(), Ordering::Release, Ordering::Relaxed)
                 .is_ok()
             {
                 let res = unsafe { t.data.assume_init_read() };
-                unsafe { guard.defer_destroy(top) };
+                unsafe { R::retire(&guard, top) };
                 return Some(res);
             }
-            guard.repin();
+            R::repin(&mut guard);
+        }
+    }
+
+    /// Pushes every element of `iter` with a single CAS: they are linked into a private chain
+    /// first, and the chain is published on top of `head` in one go. The last element ends up
+    /// on top, as if each had been pushed in turn with nothing interleaved.
+    pub fn push_all<I: IntoIterator<Item = T>>(&self, iter: I) {
+        let mut iter = iter.into_iter();
+        let Some(first) = iter.next() else {
+            return;
+        };
+        let bottom = Box::into_raw(Node::new(first));
+        let top = iter.fold(bottom, |below, t| {
+            Box::into_raw(Box::new(Node {
+                data: MaybeUninit::new(t),
+                next: below,
+            }))
+        });
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // The chain is still private, so its bottom can be relinked on every retry.
+            unsafe { (*bottom).next = head };
+            match self
+                .head
+                .compare_exchange(head, top, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
         }
     }
 
+    /// Empties the stack with a single swap of `head`, returning its elements top first.
+    ///
+    /// The nodes are retired once the iterator is dropped, since a concurrent pop may still
+    /// be reading the old top.
+    pub fn take_all(&self) -> TakeAll<T, R> {
+        let top = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        TakeAll {
+            top,
+            curr: top,
+            _reclaimer: PhantomData,
+        }
+    }
+
+    /// One push attempt: a single CAS on `head`, handing `node` back if `head` moved.
+    pub(crate) fn try_push(&self, node: Box<Node<T>>) -> Result<(), Box<Node<T>>> {
+        let top = self.head.load(Ordering::Relaxed);
+        let node = Box::into_raw(node);
+        unsafe { (*node).next = top };
+        self.head
+            .compare_exchange(top, node, Ordering::Release, Ordering::Relaxed)
+            .map(|_| ())
+            .map_err(|_| unsafe { Box::from_raw(node) })
+    }
+
+    /// One pop attempt: `Ok(None)` if the stack is empty, `Err(())` if the CAS on `head` lost.
+    pub(crate) fn try_pop(&self, guard: &R::Guard) -> Result<Option<T>, ()> {
+        let top = R::protect(guard, 0, &self.head);
+        let Some(t) = (unsafe { top.as_ref() }) else {
+            return Ok(None);
+        };
+        self.head
+            .compare_exchange(top, t.next.cast_mut(), Ordering::Release, Ordering::Relaxed)
+            .map_err(|_| ())?;
+        let res = unsafe { t.data.assume_init_read() };
+        unsafe { R::retire(guard, top) };
+        Ok(Some(res))
+    }
+
     pub fn is_empty(&self) -> bool {
-        let guard = crossbeam_epoch::pin();
-        self.head.load(Ordering::Acquire, &guard).is_null()
+        self.head.load(Ordering::Acquire).is_null()
+    }
+
+    /// Iterates from the top without removing anything.
+    ///
+    /// Only a snapshot under concurrent use: elements popped meanwhile may still be
+    /// yielded, and elements pushed after it was created are not. `T: Copy` for the same
+    /// reason as [`Queue::peek`](crate::msqueue::Queue::peek).
+    pub fn iter<'g>(&self, guard: &'g R::Guard) -> Iter<'g, T>
+    where
+        T: Copy,
+        R: Optimistic,
+    {
+        Iter {
+            curr: R::protect(guard, 0, &self.head),
+            _guard: PhantomData,
+        }
+    }
+
+    /// Pops elements from the top until the stack is found empty.
+    pub fn drain(&self) -> Drain<'_, T, R> {
+        Drain { stack: self }
     }
 }
 
-impl<T> Drop for Stack<T> {
+impl<T, R: Reclaimer> Drop for Stack<T, R> {
     fn drop(&mut self) {
-        let mut curr = mem::take(&mut self.head);
-        while let Some(c) = unsafe {curr.try_into_owned()}.map(|o|{o.into_box()}) {
-            drop(unsafe {c.data.assume_init()});
-            curr = c.next.into();
+        let mut curr = self.head.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let c = unsafe { Box::from_raw(curr) };
+            drop(unsafe { c.data.assume_init() });
+            curr = c.next.cast_mut();
         }
     }
 }
 
+pub struct Iter<'g, T> {
+    /// Nodes stay allocated while the guard is held, even if they are popped meanwhile.
+    curr: *const Node<T>,
+    _guard: PhantomData<&'g T>,
+}
 
+impl<'g, T: Copy> Iterator for Iter<'g, T> {
+    type Item = &'g T;
 
+    fn next(&mut self) -> Option<&'g T> {
+        let node = unsafe { self.curr.as_ref()? };
+        self.curr = node.next;
+        Some(unsafe { node.data.assume_init_ref() })
+    }
+}
 
+pub struct Drain<'a, T, R: Reclaimer = Epoch> {
+    stack: &'a Stack<T, R>,
+}
+
+impl<T, R: Reclaimer> Iterator for Drain<'_, T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stack.pop()
+    }
+}
+
+/// Pops the elements in LIFO order; whatever is left is dropped with the iterator.
+pub struct IntoIter<T, R: Reclaimer = Epoch> {
+    stack: Stack<T, R>,
+}
+
+impl<T, R: Reclaimer> Iterator for IntoIter<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.stack.pop()
+    }
+}
+
+impl<T, R: Reclaimer> IntoIterator for Stack<T, R> {
+    type Item = T;
+    type IntoIter = IntoIter<T, R>;
+
+    fn into_iter(self) -> IntoIter<T, R> {
+        IntoIter { stack: self }
+    }
+}
+
+/// Pushes in iteration order, so the last element ends up on top.
+impl<T, R: Reclaimer> FromIterator<T> for Stack<T, R> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut stack = Stack::with_reclaimer();
+        stack.extend(iter);
+        stack
+    }
+}
+
+/// Pushes all of `iter` with a single CAS through [`Stack::push_all`].
+impl<T, R: Reclaimer> Extend<T> for Stack<T, R> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.push_all(iter);
+    }
+}
+
+/// Iterator returned by [`Stack::take_all`], owning the detached chain.
+pub struct TakeAll<T, R: Reclaimer = Epoch> {
+    top: *mut Node<T>,
+    /// Next node whose element has not been moved out yet.
+    curr: *mut Node<T>,
+    _reclaimer: PhantomData<R>,
+}
+
+unsafe impl<T: Send, R: Reclaimer> Send for TakeAll<T, R> {}
+
+impl<T, R: Reclaimer> Iterator for TakeAll<T, R> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let node = unsafe { self.curr.as_ref()? };
+        self.curr = node.next.cast_mut();
+        Some(unsafe { node.data.assume_init_read() })
+    }
+}
+
+impl<T, R: Reclaimer> Drop for TakeAll<T, R> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+        // The elements are gone; only the node memory is left, for whoever still protects it.
+        let guard = R::pin();
+        let mut curr = self.top;
+        while !curr.is_null() {
+            let next = unsafe { (*curr).next.cast_mut() };
+            unsafe { R::retire(&guard, curr) };
+            curr = next;
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use crate::testutil::DropCounts;
+
+    #[test]
+    fn iterators_collect_drain_and_snapshot() {
+        let mut stack: Stack<_> = (0..5).collect();
+        stack.extend(5..10);
+        assert_eq!(
+            stack
+                .iter(&crossbeam_epoch::pin())
+                .copied()
+                .collect::<Vec<_>>(),
+            (0..10).rev().collect::<Vec<_>>()
+        );
+        assert_eq!(stack.drain().take(3).collect::<Vec<_>>(), [9, 8, 7]);
+        assert_eq!(
+            stack.into_iter().collect::<Vec<_>>(),
+            (0..7).rev().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn into_iter_drops_the_rest() {
+        let counts = DropCounts::new(10);
+        let mut it = (0..10)
+            .map(|v| counts.track(v))
+            .collect::<Stack<_>>()
+            .into_iter();
+        assert_eq!(it.next().map(|t| t.value), Some(9));
+        drop(it);
+        counts.assert_all_dropped();
+    }
+
+    #[test]
+    fn take_all_drops_the_rest() {
+        let counts = DropCounts::new(10);
+        let stack = Stack::new();
+        stack.push_all((0..5).map(|v| counts.track(v)));
+        stack.push(counts.track(5));
+        stack.push_all((6..10).map(|v| counts.track(v)));
+        let mut taken = stack.take_all();
+        assert!(stack.is_empty());
+        let top: Vec<_> = taken.by_ref().take(5).map(|t| t.value).collect();
+        assert_eq!(top, [9, 8, 7, 6, 5]);
+        drop(taken);
+        counts.assert_all_dropped();
+        assert_eq!(stack.take_all().next().map(|t| t.value), None);
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    cell::UnsafeCell,
+    mem::{self, MaybeUninit},
+    ptr,
+};
+
+use crossbeam_utils::CachePadded;
+
+use crate::{
+    rawlock::RawLock,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+struct Node<T> {
+    data: MaybeUninit<T>,
+    /// The only field both ends touch: a push links here while a pop on a one-node queue
+    /// checks it, each under its own lock.
+    next: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(data: MaybeUninit<T>) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            data,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+/// One end of the queue: a node pointer only touched while holding `lock`.
+struct End<T, L> {
+    lock: L,
+    node: UnsafeCell<*mut Node<T>>,
+}
+
+impl<T, L: RawLock> End<T, L> {
+    fn with<R>(&self, f: impl FnOnce(&mut *mut Node<T>) -> R) -> R {
+        let token = self.lock.lock();
+        let r = f(unsafe { &mut *self.node.get() });
+        self.lock.unlock(token);
+        r
+    }
+}
+
+/// The two-lock blocking queue from Michael and Scott's paper, generic over the crate's
+/// locks.
+///
+/// Producers serialize on the tail lock and consumers on the head lock, so a push and a pop
+/// never wait for each other; a dummy node at the head keeps the two ends apart even when
+/// the queue is empty.
+pub struct TwoLockQueue<T, L> {
+    head: CachePadded<End<T, L>>,
+    tail: CachePadded<End<T, L>>,
+}
+
+unsafe impl<T: Send, L: RawLock> Sync for TwoLockQueue<T, L> {}
+unsafe impl<T: Send, L: RawLock> Send for TwoLockQueue<T, L> {}
+
+impl<T, L: RawLock> Default for TwoLockQueue<T, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, L: RawLock> TwoLockQueue<T, L> {
+    pub fn new() -> Self {
+        let dummy = Node::new(MaybeUninit::uninit());
+        Self {
+            head: CachePadded::new(End {
+                lock: L::default(),
+                node: UnsafeCell::new(dummy),
+            }),
+            tail: CachePadded::new(End {
+                lock: L::default(),
+                node: UnsafeCell::new(dummy),
+            }),
+        }
+    }
+
+    pub fn push(&self, t: T) {
+        let node = Node::new(MaybeUninit::new(t));
+        self.tail.with(|tail| {
+            unsafe { (**tail).next.store(node, Ordering::Release) };
+            *tail = node;
+        });
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let old = self.head.with(|head| {
+            let next = unsafe { (**head).next.load(Ordering::Acquire) };
+            if next.is_null() {
+                return None;
+            }
+            // `next` becomes the dummy; its value moves out and the old dummy is freed.
+            let t = unsafe { (*next).data.assume_init_read() };
+            Some((mem::replace(head, next), t))
+        });
+        old.map(|(dummy, t)| {
+            drop(unsafe { Box::from_raw(dummy) });
+            t
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.head
+            .with(|head| unsafe { (**head).next.load(Ordering::Acquire) }.is_null())
+    }
+}
+
+impl<T, L> Drop for TwoLockQueue<T, L> {
+    fn drop(&mut self) {
+        unsafe {
+            let dummy = *self.head.node.get_mut();
+            let mut curr = (*dummy).next.load(Ordering::Relaxed);
+            drop(Box::from_raw(dummy));
+            while !curr.is_null() {
+                let node = Box::from_raw(curr);
+                curr = node.next.load(Ordering::Relaxed);
+                drop(node.data.assume_init());
+            }
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Small helpers shared by modules that have nothing else in common.
+
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+thread_local! {
+    static RNG: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// xorshift64, seeded per thread: a cheap pick in `0..n` for where `Select`'s readiness scan
+/// starts, and for which elimination slot a stack operation tries.
+pub(crate) fn random(n: usize) -> usize {
+    RNG.with(|rng| {
+        let mut x = rng.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        rng.set(x);
+        (x % n as u64) as usize
+    })
+}